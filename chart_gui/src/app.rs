@@ -1,4 +1,5 @@
 use eframe::egui;
+use gaitool_rs::utils::contact::ContactPolicy;
 
 use crate::chart::Chart;
 
@@ -37,6 +38,16 @@ impl eframe::App for App {
                         frame.close();
                     }
                 });
+                ui.menu_button("Settings", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Contact: ");
+                        ui.text_edit_singleline(&mut self.chart.contact);
+                    });
+                    if let Err(e) = self.chart.contact.parse::<ContactPolicy>()
+                    {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                });
                 ui.with_layout(
                     egui::Layout::right_to_left(egui::Align::Center),
                     |ui| {
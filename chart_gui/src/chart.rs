@@ -8,6 +8,8 @@ use eframe::egui::{
     ScrollArea,
 };
 
+use gaitool_rs::utils::contact::ContactPolicy;
+
 use crate::{
     config::{Position, Variable},
     data_process::{DataInfo, Manager, Message},
@@ -42,6 +44,8 @@ pub struct Chart {
     manager: Manager,
     result: Arc<Mutex<Message>>,
     file_selects: Vec<bool>,
+    /// contact policy text, parsed when files are opened
+    pub contact: String,
     pub state: State,
 }
 
@@ -55,6 +59,7 @@ impl Chart {
             pos: Position::L,
             var: Variable::AccelX,
             file_selects: Vec::new(),
+            contact: ContactPolicy::default().to_string(),
             result,
             manager,
             state: State {
@@ -79,14 +84,29 @@ impl Chart {
     pub fn open_dir(&mut self) {
         if let Some(path) = rfd::FileDialog::new().pick_folder() {
             self.state.show_process_win = true;
-            self.manager.start_get_data_from_dir(path);
+            if let Some(contact) = self.contact_policy() {
+                self.manager.start_get_data_from_dir(path, contact);
+            }
         }
     }
 
     pub fn open_file(&mut self) {
         if let Some(path) = rfd::FileDialog::new().pick_file() {
             self.state.show_process_win = true;
-            self.manager.start_get_data_from_file(path);
+            if let Some(contact) = self.contact_policy() {
+                self.manager.start_get_data_from_file(path, contact);
+            }
+        }
+    }
+
+    /// parse contact policy, show abort message if it's invalid
+    fn contact_policy(&mut self) -> Option<ContactPolicy> {
+        match self.contact.parse() {
+            Ok(contact) => Some(contact),
+            Err(e) => {
+                *self.result.lock().unwrap() = Message::Abort(e);
+                None
+            }
         }
     }
 
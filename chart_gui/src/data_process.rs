@@ -1,5 +1,17 @@
 use crate::config::{Position, Variable};
-use polars::{io::csv::CsvReader, prelude::*};
+use gaitool_rs::{
+    error::{GaitError, Result as GaitResult},
+    utils::{
+        contact::ContactPolicy,
+        discover::Discovery,
+        gait::{GaitEvents, Phase, Side},
+        header::SELECTION_KEY,
+        noraxon::NoraxonReader,
+        preprocess::{remap_contact, LT_CONTACT, RT_CONTACT},
+        remap::Language,
+    },
+};
+use polars::prelude::*;
 use std::{
     collections::HashMap,
     path::Path,
@@ -10,6 +22,10 @@ use std::{
     thread,
 };
 
+/// remap table of Chinese raw exports, English files and files already
+/// split are read as is
+const CHINESE_TABLE: &str = "assets/name.json";
+
 #[derive(Default, Debug)]
 pub struct Quantile<T: Copy> {
     min: T,
@@ -96,7 +112,11 @@ impl Manager {
         self.external_sender.send(Message::Nothing).unwrap();
     }
 
-    pub fn start_get_data_from_file<P: AsRef<Path> + 'static>(&self, file: P) {
+    pub fn start_get_data_from_file<P: AsRef<Path> + 'static>(
+        &self,
+        file: P,
+        contact: ContactPolicy,
+    ) {
         let external_sender = self.external_sender.clone();
         let must_stop = self.must_stop.clone();
         let file = file.as_ref().to_owned();
@@ -115,34 +135,18 @@ impl Manager {
                     format!("{}", file.file_name().unwrap().to_str().unwrap()),
                 ))
                 .unwrap();
-            let info = Self::extract_info(&file);
-            if info[0].len() != 12 || info[1].len() != 12 {
-                external_sender
-                    .send(Message::Abort("info not 12 len".to_owned()))
-                    .unwrap();
-                return;
-            }
-            if info[0][5] != String::from("exported with version") {
-                external_sender
-                    .send(Message::Abort("no version info".to_owned()))
-                    .unwrap();
-                return;
-            }
-            let selection = info[1][11]
-                .split(" ")
-                .collect::<Vec<&str>>()
-                .iter()
-                .map(|s| {
-                    s.split("-")
-                        .collect::<Vec<&str>>()
-                        .iter()
-                        .map(|n| n.parse::<f64>().unwrap())
-                        .collect::<Vec<f64>>()
-                })
-                .collect::<Vec<Vec<f64>>>();
+            let raw = match RawData::parse_file(&file, &contact) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    external_sender
+                        .send(Message::Abort(e.to_string()))
+                        .unwrap();
+                    return;
+                }
+            };
             file_lists.push(DataInfo {
                 path: file.file_name().unwrap().to_str().unwrap().to_owned(),
-                raw: RawData::parse_file(file, selection).unwrap(),
+                raw,
             });
             external_sender.send(Message::Done(file_lists)).unwrap();
         });
@@ -153,17 +157,29 @@ impl Manager {
     >(
         &self,
         input_dir: P,
+        contact: ContactPolicy,
     ) {
         let external_sender = self.external_sender.clone();
         let must_stop = self.must_stop.clone();
         must_stop.store(false, Ordering::Relaxed);
         thread::spawn(move || {
             let mut file_lists = Vec::new();
-            let files: Vec<std::fs::DirEntry> = std::fs::read_dir(input_dir)
-                .unwrap()
-                .map(|e| e.unwrap())
-                .collect();
-            for (i, file) in files.iter().enumerate() {
+            let files = match Discovery::new(input_dir).find() {
+                Ok(files) => files,
+                Err(e) => {
+                    external_sender
+                        .send(Message::Abort(e.to_string()))
+                        .unwrap();
+                    return;
+                }
+            };
+            for (i, found) in files.iter().enumerate() {
+                /* shown relative to picked dir */
+                let name = found
+                    .relative
+                    .join(found.path.file_name().unwrap())
+                    .display()
+                    .to_string();
                 if must_stop.load(Ordering::Relaxed) {
                     external_sender
                         .send(Message::Abort("Stopped!".into()))
@@ -173,197 +189,85 @@ impl Manager {
                 external_sender
                     .send(Message::Running(
                         (i as f32 + 1.) / files.len() as f32,
-                        format!("{}", file.file_name().into_string().unwrap()),
+                        name.clone(),
                     ))
                     .unwrap();
-                let info = Self::extract_info(file.path());
-                if info[0].len() != 12 || info[1].len() != 12 {
-                    external_sender
-                        .send(Message::Abort("info not 12 len".to_owned()))
-                        .unwrap();
-                    break;
-                }
-                if info[0][5] != String::from("exported with version") {
-                    external_sender
-                        .send(Message::Abort("no version info".to_owned()))
-                        .unwrap();
-                    break;
-                }
-                let selection = info[1][11]
-                    .split(" ")
-                    .collect::<Vec<&str>>()
-                    .iter()
-                    .map(|s| {
-                        s.split("-")
-                            .collect::<Vec<&str>>()
-                            .iter()
-                            .map(|n| n.parse::<f64>().unwrap())
-                            .collect::<Vec<f64>>()
-                    })
-                    .collect::<Vec<Vec<f64>>>();
-                file_lists.push(DataInfo {
-                    path: file.file_name().to_str().unwrap().to_string(),
-                    raw: RawData::parse_file(file.path(), selection).unwrap(),
-                })
+                let raw = match RawData::parse_file(&found.path, &contact) {
+                    Ok(raw) => raw,
+                    Err(e) => {
+                        external_sender
+                            .send(Message::Abort(e.to_string()))
+                            .unwrap();
+                        break;
+                    }
+                };
+                file_lists.push(DataInfo { path: name, raw })
             }
             external_sender.send(Message::Done(file_lists)).unwrap();
         });
     }
-
-    pub fn extract_info<P: AsRef<std::path::Path>>(
-        path: P,
-    ) -> Vec<Vec<String>> {
-        let raw_file = std::fs::File::open(path).expect("Can't open raw file");
-        let reader_raw = std::io::BufReader::new(raw_file);
-        let mut rdr = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_reader(reader_raw);
-        let info: Vec<Vec<String>> = rdr
-            .records()
-            .take(2)
-            .map(|row| {
-                let r = row.expect("a valid csv entry");
-                let mut v = Vec::new();
-                for i in 0..r.len() {
-                    let range = r.range(i).expect("a range");
-                    let value = &r.as_slice()[range];
-                    v.push(value.to_string().clone());
-                }
-                v
-            })
-            .collect();
-        info
-    }
 }
 
 impl RawData {
     pub fn parse_file<P: AsRef<std::path::Path>>(
         path: P,
-        selections: Vec<Vec<f64>>,
-    ) -> Result<Self> {
-        let raw_df = CsvReader::from_path(path.as_ref())?
-            .with_skip_rows(3)
-            .finish()?;
-
-        let contact_df = raw_df
-            .clone()
-            .lazy()
-            .select(vec![
-                col("time"),
-                when(
-                    col("Noraxon MyoMotion-Segments-Foot LT-Contact")
-                        .eq(lit::<i32>(1000)),
-                )
-                .then(lit(true))
-                .otherwise(lit(false))
-                .alias("LT"),
-                when(
-                    col("Noraxon MyoMotion-Segments-Foot RT-Contact")
-                        .eq(lit::<i32>(1000)),
-                )
-                .then(lit(true))
-                .otherwise(lit(false))
-                .alias("RT"),
-            ])
-            .with_column(col("LT").and(col("RT")).alias("DB"))
-            .with_column(not(col("DB")).alias("SG"))
-            .with_columns(vec![
-                col("LT").and(col("SG")).alias("LS"),
-                col("RT").and(col("SG")).alias("RS"),
-            ])
-            .collect()?;
+        contact: &ContactPolicy,
+    ) -> GaitResult<Self> {
+        let mut reader = NoraxonReader::new(path.as_ref());
+        if Path::new(CHINESE_TABLE).is_file() {
+            reader =
+                reader.with_language_table(Language::Chinese, CHINESE_TABLE);
+        }
+        let recording = reader.finish()?;
+        let selections: Vec<Vec<f64>> = match &recording.header.selection {
+            Some(selection) => {
+                selection.iter().map(|(s, e)| vec![*s, *e]).collect()
+            }
+            None => {
+                return Err(GaitError::MissingHeaderField {
+                    path: path.as_ref().to_path_buf(),
+                    field: SELECTION_KEY.to_owned(),
+                })
+            }
+        };
+        let raw_df = recording.data;
 
-        let contact_df = contact_df
-            .lazy()
-            // DB start/end
-            .with_column(col("DB").shift(1).alias("first"))
-            .with_column(col("DB").alias("second"))
-            .with_columns(vec![
-                when(not(col("first")).and(col("second")))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("DB_S"),
-                when(col("first").and(not(col("second"))))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("DB_E"),
-            ])
-            .drop_columns(["first", "second"])
-            // LT start/end
-            .with_column(col("LT").shift(1).alias("first"))
-            .with_column(col("LT").alias("second"))
-            .with_columns(vec![
-                when(not(col("first")).and(col("second")))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("LT_S"),
-                when(col("first").and(not(col("second"))))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("LT_E"),
-            ])
-            .drop_columns(["first", "second"])
-            // RT start/end
-            .with_column(col("RT").shift(1).alias("first"))
-            .with_column(col("RT").alias("second"))
-            .with_columns(vec![
-                when(not(col("first")).and(col("second")))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("RT_S"),
-                when(col("first").and(not(col("second"))))
-                    .then(lit::<i32>(1))
-                    .otherwise(lit::<i32>(0))
-                    .alias("RT_E"),
-            ])
-            .drop_columns(["first", "second"])
-            .drop_nulls(None)
-            .collect()?;
+        let contact_df = remap_contact(raw_df.clone(), contact)
+            .map_err(|e| e.in_file(&path))?;
+        let events = GaitEvents::from_contact(&contact_df)
+            .map_err(|e| e.in_file(&path))?;
+        let strides = events.gait().map_err(|e| e.in_file(&path))?;
+        // phase in one of the selection
+        let in_selection = |phases: Vec<Phase>| {
+            phases
+                .into_iter()
+                .filter(|p| {
+                    selections
+                        .iter()
+                        .any(|sel| sel[0] <= p.start && p.end <= sel[1])
+                })
+                .collect::<Vec<Phase>>()
+        };
+        let durations = |phases: Vec<Phase>| {
+            in_selection(phases)
+                .iter()
+                .map(Phase::duration)
+                .collect::<Vec<f64>>()
+        };
 
-        let gait = contact_df
-            .clone()
-            .lazy()
-            .select([col("time"), col("DB_S")])
-            .filter(col("DB_S").eq(1))
-            .collect()?
-            // get db start time to vec
-            .column("time")?
-            .f64()?
-            .into_no_null_iter()
-            // get every 2 db
-            .step_by(2)
-            .collect::<Vec<f64>>();
-        let gait_ranges = gait
-            // create start end
-            .windows(2)
-            .map(|s| s.to_vec())
-            // valid range from selction
-            .filter(|r| {
-                // r in one of the selection
-                for sel in selections.iter() {
-                    if sel[0] <= r[0] && r[1] <= sel[1] {
-                        return true;
-                    }
-                }
-                false
-            })
+        let mut gait = strides.iter().map(|p| p.start).collect::<Vec<f64>>();
+        gait.extend(strides.last().map(|p| p.end));
+        let gait_ranges = in_selection(strides)
+            .iter()
+            .map(|p| vec![p.start, p.end])
             .collect::<Vec<Vec<f64>>>();
         let gait_gaps = gait_ranges
             .iter()
             .map(|v| v[1] - v[0])
             .collect::<Vec<f64>>();
-        let db_gaps = get_support_range(contact_df.clone(), "DB", &selections)?
-            .into_iter()
-            .map(|v| v[1] - v[0])
-            .collect::<Vec<f64>>();
-        let lt_gaps = get_support_range(contact_df.clone(), "LT", &selections)?
-            .into_iter()
-            .map(|v| v[1] - v[0])
-            .collect::<Vec<f64>>();
-        let rt_gaps = get_support_range(contact_df.clone(), "RT", &selections)?
-            .into_iter()
-            .map(|v| v[1] - v[0])
-            .collect::<Vec<f64>>();
+        let db_gaps = durations(events.double_supports());
+        let lt_gaps = durations(events.single_support(Side::Left));
+        let rt_gaps = durations(events.single_support(Side::Right));
 
         let x = raw_df
             .column("time")?
@@ -396,16 +300,9 @@ impl RawData {
             x,
             y,
             selections,
-            l_contact: raw_df
-                .column("Noraxon MyoMotion-Segments-Foot LT-Contact")?
-                .i64()?
-                .into_no_null_iter()
-                .collect::<Vec<i64>>(),
-            r_contact: raw_df
-                .column("Noraxon MyoMotion-Segments-Foot RT-Contact")?
-                .i64()?
-                .into_no_null_iter()
-                .collect::<Vec<i64>>(),
+            // decoded contact drawn in the original 0/1000 scale
+            l_contact: decoded_contact(&contact_df, LT_CONTACT)?,
+            r_contact: decoded_contact(&contact_df, RT_CONTACT)?,
             gait: (gait, get_quantile(&gait_gaps)?),
             db: get_quantile(&db_gaps)?,
             lt: get_quantile(&lt_gaps)?,
@@ -414,46 +311,13 @@ impl RawData {
     }
 }
 
-fn get_support_range(
-    contact_df: DataFrame, // support dataframe which has DB/LT/RT start/end
-    pos: &str,
-    selections: &Vec<Vec<f64>>,
-) -> Result<Vec<Vec<f64>>> {
-    let ranges = contact_df
-        .lazy()
-        .select([
-            col("time"),
-            col(format!("{pos}_S").as_str()),
-            col(format!("{pos}_E").as_str()),
-        ])
-        .filter(
-            col(format!("{pos}_S").as_str())
-                .eq(1)
-                .or(col(format!("{pos}_E").as_str()).eq(1)),
-        )
-        .collect()?
-        .column("time")?
-        .f64()?
+fn decoded_contact(contact_df: &DataFrame, foot: &str) -> Result<Vec<i64>> {
+    Ok(contact_df
+        .column(foot)?
+        .bool()?
         .into_no_null_iter()
-        .collect::<Vec<f64>>();
-    assert_eq!(ranges.len() % 2, 0);
-
-    let ranges = (&ranges[1..ranges.len() - 1])
-        .to_vec()
-        .windows(2)
-        .map(|s| s.to_vec())
-        .step_by(2)
-        .filter(|r| {
-            // r in one of the selection
-            for sel in selections.iter() {
-                if sel[0] <= r[0] && r[1] <= sel[1] {
-                    return true;
-                }
-            }
-            false
-        })
-        .collect::<Vec<Vec<f64>>>();
-    Ok(ranges)
+        .map(|c| if c { 1000 } else { 0 })
+        .collect())
 }
 
 pub fn get_min_max_quantile(
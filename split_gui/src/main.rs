@@ -1,6 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use gaitool_rs::core::split::split;
+use gaitool_rs::core::split::{split, SplitOptions};
+use gaitool_rs::utils::batch::par_map_progress;
+use gaitool_rs::utils::contact::ContactPolicy;
+use gaitool_rs::utils::discover::{Discovery, Found};
+use gaitool_rs::utils::recording::{Posture, RecordingId};
 use std::{
     path::PathBuf,
     sync::{Arc, Mutex},
@@ -9,7 +13,7 @@ use std::{
 use eframe::egui;
 fn main() {
     let options = eframe::NativeOptions {
-        initial_window_size: Some(egui::Vec2 { x: 400., y: 160. }),
+        initial_window_size: Some(egui::Vec2 { x: 400., y: 190. }),
         drag_and_drop_support: true,
         ..Default::default()
     };
@@ -34,10 +38,10 @@ struct Proc {
 
 struct AppState {
     slider_value: u32,
+    contact: String,
     process: Proc,
     picked_dir: Option<String>,
     saved_dir: Option<String>,
-    sub_dirs: Vec<PathBuf>,
 }
 
 impl AppState {
@@ -54,10 +58,10 @@ impl AppState {
         load_fonts(&cc.egui_ctx);
         Self {
             slider_value: 70,
+            contact: ContactPolicy::default().to_string(),
             process,
             picked_dir: None,
             saved_dir: None,
-            sub_dirs: Vec::new(),
         }
     }
 }
@@ -85,18 +89,6 @@ impl eframe::App for AppState {
                             {
                                 self.picked_dir =
                                     Some(path.display().to_string());
-                                self.sub_dirs = std::fs::read_dir(&path)
-                                    .unwrap()
-                                    .into_iter()
-                                    .filter(|d| {
-                                        d.as_ref()
-                                            .unwrap()
-                                            .metadata()
-                                            .unwrap()
-                                            .is_dir()
-                                    })
-                                    .map(|d| d.unwrap().path())
-                                    .collect();
                                 self.saved_dir = Some(
                                     path.join("output").display().to_string(),
                                 );
@@ -130,13 +122,23 @@ impl eframe::App for AppState {
                         egui::Slider::new(&mut self.slider_value, 0..=100)
                             .text("%"),
                     );
-                })
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Contact: ");
+                    ui.text_edit_singleline(&mut self.contact);
+                    if let Err(e) = self.contact.parse::<ContactPolicy>() {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                });
             });
+            let contact = self.contact.parse::<ContactPolicy>();
             let p_state = self.process.state.lock().unwrap();
             ui.group(|ui| {
                 ui.horizontal(|ui| {
                     ui.label("Status: ");
-                    ui.add_visible_ui(p_state.is_running, |ui| {
+                    // error of a failed run stays until next start
+                    let visible = p_state.is_running || p_state.msg.is_some();
+                    ui.add_visible_ui(visible, |ui| {
                         if let Some(dir) = &p_state.msg {
                             ui.label(dir);
                         }
@@ -150,14 +152,23 @@ impl eframe::App for AppState {
             });
             ui.vertical_centered(|ui| {
                 ui.add_enabled_ui(
-                    !p_state.is_running && self.picked_dir.is_some(),
+                    !p_state.is_running
+                        && self.picked_dir.is_some()
+                        && contact.is_ok(),
                     |ui| {
                         if ui.button(" Start ").clicked() {
+                            let opts = SplitOptions {
+                                contact: contact.unwrap_or_default(),
+                                ..SplitOptions::new(
+                                    self.slider_value as usize,
+                                    "assets",
+                                )
+                            };
                             run_split(
                                 self.process.sx.clone(),
-                                self.sub_dirs.clone(),
+                                self.picked_dir.clone(),
                                 self.saved_dir.clone(),
-                                self.slider_value as usize,
+                                opts,
                             );
                         }
                     },
@@ -168,21 +179,15 @@ impl eframe::App for AppState {
             let path = ctx.input().raw.dropped_files[0].clone().path.unwrap();
             self.picked_dir = Some(path.display().to_string());
             self.saved_dir = Some(path.join("output").display().to_string());
-            self.sub_dirs = std::fs::read_dir(&path)
-                .unwrap()
-                .into_iter()
-                .filter(|d| d.as_ref().unwrap().metadata().unwrap().is_dir())
-                .map(|d| d.unwrap().path())
-                .collect();
         }
     }
 }
 
 fn run_split(
     sender: std::sync::mpsc::Sender<ProcState>,
-    dirs: Vec<PathBuf>,
+    picked_dir: Option<String>,
     saved_dir: Option<String>,
-    percent: usize,
+    opts: SplitOptions,
 ) {
     std::thread::spawn(move || {
         let mut p_state = ProcState {
@@ -190,28 +195,41 @@ fn run_split(
             percentage: 0.,
             msg: None,
         };
-        let num_works = dirs.len() + 1;
         let saved_dir = PathBuf::from(saved_dir.as_ref().unwrap());
-        let loading_chars = vec![".", "..", "..."];
-        let mut chars_it = loading_chars.iter().cycle();
-        for (i, dir) in dirs.iter().enumerate() {
-            println!("{:?}", dir);
-            if dir.file_name().unwrap() == PathBuf::from("output") {
-                continue;
+        /* recordings in every sub dir, save dir is never walked */
+        let files = match Discovery::new(picked_dir.as_ref().unwrap())
+            .skip(&saved_dir)
+            .find()
+        {
+            Ok(files) => files,
+            Err(e) => {
+                p_state.is_running = false;
+                p_state.msg = Some(e.to_string());
+                sender.send(p_state).unwrap();
+                return;
             }
-            p_state.percentage = (i + 1) as f32 / num_works as f32;
-            p_state.msg = Some(format!(
-                "Splitting {} {}",
-                dir.display().to_string(),
-                chars_it.next().unwrap()
-            ));
-            sender.send(p_state.clone()).unwrap();
-
-            for file in std::fs::read_dir(dir).unwrap() {
-                let file = file.unwrap().path();
-                let filename =
-                    file.file_name().unwrap().to_str().unwrap().to_string();
-                p_state.msg = Some(format!(
+        };
+        let num_works = files.len() + 1;
+        let split_found = |found: &Found| {
+            let file = &found.path;
+            let id = match RecordingId::from_path(file) {
+                Ok(id) => Some(id),
+                Err(_) => None,
+            };
+            id.map(|id| {
+                let saved_dir = if id.posture == Posture::Walking {
+                    saved_dir.join("走路")
+                } else {
+                    saved_dir.join("跑步機")
+                };
+                split(file, &found.mirror(&saved_dir), &opts, None)
+            })
+        };
+        par_map_progress(&files, 0, split_found, |found, result, i| {
+            let file = &found.path;
+            let msg = match result {
+                Some(Err(e)) => e.to_string(),
+                _ => format!(
                     "Splitting {} {}",
                     file.parent()
                         .unwrap()
@@ -219,31 +237,17 @@ fn run_split(
                         .unwrap()
                         .to_str()
                         .unwrap(),
-                    filename
-                ));
-                sender.send(p_state.clone()).unwrap();
-                let name_vec = filename.split("-").collect::<Vec<&str>>();
-                if name_vec.len() < 10 {
-                    continue;
-                }
-                println!("{:?}", name_vec);
-                let saved_dir = if name_vec[6] == "1" {
-                    saved_dir.join("走路")
-                } else {
-                    saved_dir.join("跑步機")
-                };
-                if let Err(e) = split(
-                    &file,
-                    &saved_dir,
-                    percent,
-                    &PathBuf::from("assets"),
-                    None,
-                ) {
-                    p_state.msg = Some(e.to_string());
-                    sender.send(p_state.clone()).unwrap();
-                }
-            }
-        }
+                    file.file_name().unwrap().to_str().unwrap()
+                ),
+            };
+            sender
+                .send(ProcState {
+                    is_running: true,
+                    percentage: i as f32 / num_works as f32,
+                    msg: Some(msg),
+                })
+                .unwrap();
+        });
 
         p_state.percentage = 1.;
         p_state.msg = Some("Finished".to_string());
@@ -251,6 +255,7 @@ fn run_split(
         std::thread::sleep(std::time::Duration::from_secs(1));
 
         p_state.is_running = false;
+        p_state.msg = None;
         sender.send(p_state.clone()).unwrap();
     });
 }
@@ -1,4 +1,8 @@
 use clap::{Args, Parser, Subcommand};
+use gaitool_rs::utils::contact::ContactPolicy;
+use gaitool_rs::utils::events::ContactSource;
+use gaitool_rs::utils::gait::Side;
+use gaitool_rs::utils::quality::RejectPolicy;
 use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
@@ -23,25 +27,93 @@ pub enum Commands {
     /// batch check if header and file num correct
     #[clap(arg_required_else_help = true)]
     Check(Check),
-    /// batch de-identify header info
+    /// batch de-identify header info and file names
     #[clap(arg_required_else_help = true)]
     Clean(Clean),
-    ///
+    /// restore de-identified recordings with their pseudonym map
+    #[clap(arg_required_else_help = true)]
+    Reidentify(Reidentify),
+    /// diff file columns against remap table
     #[clap(arg_required_else_help = true)]
     Diff(Diff),
+    /// validate remap table, optionally against a recording
+    #[clap(arg_required_else_help = true)]
+    RemapCheck(RemapCheck),
+    /// resample selected strides to 0-100% gait cycle
+    #[clap(arg_required_else_help = true)]
+    Normalize(Normalize),
+    /// show how an output file was made
+    #[clap(arg_required_else_help = true)]
+    Provenance(Provenance),
 }
 
 #[derive(Debug, Args)]
 pub struct Export {
     /// input file
+    #[clap(short, long, required_unless_present = "dir")]
+    pub file: Option<PathBuf>,
+    /// split output directory, export every file with its `selection`
+    /// header and concat results into cohort.csv
+    #[clap(long, conflicts_with_all = &["file", "ranges", "time"])]
+    pub dir: Option<PathBuf>,
+    /// worker threads of `--dir`, 0 uses every core
+    #[clap(short, long, default_value = "0")]
+    pub jobs: usize,
+    /// process every input even if its output is up to date
+    #[clap(long)]
+    pub force: bool,
+    #[clap(flatten)]
+    pub discover: Discover,
+    /// output directory
+    #[clap(short, long, required = true)]
+    pub save: PathBuf,
+    /// selection as stride row index, e.g. "2 5", row 0 is the part
+    /// before the first heel strike
+    #[clap(short, long, parse(try_from_str = parse_range_tuple))]
+    pub ranges: Vec<(u32, u32)>,
+    /// selection as time in second, e.g. "4.37-15.965", the file's
+    /// `selection` header is used when neither this nor `-r` is given
+    #[clap(short, long, parse(try_from_str = parse_time_range), conflicts_with = "ranges")]
+    pub time: Vec<(f64, f64)>,
+    /// column profile csv, keep only its columns (e.g. "assets/filter.csv")
+    #[clap(long)]
+    pub profile: Option<PathBuf>,
+    /// foot contact decoding, e.g. "threshold:500+debounce:3"
+    #[clap(long, default_value = "exact:1000")]
+    pub contact: ContactPolicy,
+    /// contact source: sensor, imu (detect from gyroscope) or auto
+    #[clap(long, default_value = "sensor")]
+    pub events: ContactSource,
+    /// outlier stride rejection, e.g. "iqr:1.5+pattern", "mad:3", "z:2.5",
+    /// "bounds:0.8:1.6"
+    #[clap(long, default_value = "none")]
+    pub reject: RejectPolicy,
+}
+
+#[derive(Debug, Args)]
+pub struct Normalize {
+    /// input file, strides inside its selection are used
     #[clap(short, long, required = true)]
     pub file: PathBuf,
     /// output directory
     #[clap(short, long, required = true)]
     pub save: PathBuf,
-    /// selection range index. e.g. "2 5"
-    #[clap(short, long, parse(try_from_str = parse_range_tuple), required = true)]
-    pub ranges: Vec<(u32, u32)>,
+    /// strides of this foot (lt or rt), leading foot by default
+    #[clap(long)]
+    pub side: Option<Side>,
+    /// column profile csv, keep only its columns (e.g. "assets/filter.csv")
+    #[clap(long)]
+    pub profile: Option<PathBuf>,
+    /// foot contact decoding, e.g. "threshold:500+debounce:3"
+    #[clap(long, default_value = "exact:1000")]
+    pub contact: ContactPolicy,
+    /// contact source: sensor, imu (detect from gyroscope) or auto
+    #[clap(long, default_value = "sensor")]
+    pub events: ContactSource,
+    /// outlier stride rejection, e.g. "iqr:1.5+pattern", "mad:3", "z:2.5",
+    /// "bounds:0.8:1.6"
+    #[clap(long, default_value = "none")]
+    pub reject: RejectPolicy,
 }
 
 #[derive(Debug, Args)]
@@ -52,6 +124,13 @@ pub struct Concat {
     /// output directory
     #[clap(short, long, required = true)]
     pub save: PathBuf,
+    /// column profile csv, keep only stats of its channels, file info and
+    /// spatio-temporal columns are always kept (e.g. "assets/filter.csv")
+    #[clap(long)]
+    pub profile: Option<PathBuf>,
+    /// concat even if inputs didn't change since last time
+    #[clap(long)]
+    pub force: bool,
 }
 
 #[derive(Debug, Args)]
@@ -67,6 +146,80 @@ pub struct Split {
     pub percent: usize,
     #[clap(short, long, default_value = "./assets/")]
     pub remap_csv_dir: PathBuf,
+    /// extra remap table (csv, json or toml) merged over the version table,
+    /// earlier one has higher priority (e.g. "--remap assets/name.json")
+    #[clap(long)]
+    pub remap: Vec<PathBuf>,
+    /// column profile csv, keep only its columns, time and foot contacts
+    /// are always kept (e.g. "assets/filter.csv")
+    #[clap(long)]
+    pub profile: Option<PathBuf>,
+    /// foot contact decoding, e.g. "threshold:500+debounce:3"
+    #[clap(long, default_value = "exact:1000")]
+    pub contact: ContactPolicy,
+    /// contact source: sensor, imu (detect from gyroscope) or auto
+    #[clap(long, default_value = "sensor")]
+    pub events: ContactSource,
+    /// outlier stride rejection, e.g. "iqr:1.5+pattern", "mad:3", "z:2.5",
+    /// "bounds:0.8:1.6"
+    #[clap(long, default_value = "none")]
+    pub reject: RejectPolicy,
+    /// bouts of `bouts` strategy with fewer strides are skipped
+    #[clap(long, default_value = "4")]
+    pub min_bout_strides: usize,
+    /// selection strategy: centred, bouts, count:<n>, time:<start>:<end>,
+    /// skip:<first>:<last> or all
+    #[clap(long, default_value = "centred")]
+    pub strategy: String,
+    /// worker threads, 0 uses every core
+    #[clap(short, long, default_value = "0")]
+    pub jobs: usize,
+    /// process every input even if its output is up to date
+    #[clap(long)]
+    pub force: bool,
+    #[clap(flatten)]
+    pub deid: Deid,
+    #[clap(flatten)]
+    pub discover: Discover,
+}
+
+/// de-identification of split and clean
+#[derive(Debug, Args)]
+pub struct Deid {
+    /// de-identification policy (toml), drops last_name and first_name
+    /// by default
+    #[clap(long)]
+    pub policy: Option<PathBuf>,
+    /// pseudonym map (json) created or extended when policy hashes or
+    /// pseudonymises, keep it apart from output
+    #[clap(long)]
+    pub map: Option<PathBuf>,
+}
+
+/// input discovery of batch commands, sub dirs are walked and mirrored
+/// into save dir
+#[derive(Debug, Args)]
+pub struct Discover {
+    /// keep only files matching glob on path relative to input dir
+    /// (can be multiple, e.g. "--include 'visit-1/**'"), "*.csv" if not
+    /// given
+    #[clap(long)]
+    pub include: Vec<String>,
+    /// drop files matching glob on path relative to input dir
+    #[clap(long)]
+    pub exclude: Vec<String>,
+    /// keep only recordings of subject id (can be multiple)
+    #[clap(long)]
+    pub subject: Vec<String>,
+    /// keep only recordings of posture id, e.g. "1" walking, "2" treadmill
+    #[clap(long)]
+    pub posture: Vec<String>,
+    /// keep only recordings of order (can be multiple)
+    #[clap(long)]
+    pub order: Vec<String>,
+    /// only read top level of input dir
+    #[clap(long)]
+    pub flat: bool,
 }
 
 #[derive(Debug, Args)]
@@ -74,6 +227,15 @@ pub struct Check {
     /// input directory
     #[clap(short, long, required = true)]
     pub file_dir: PathBuf,
+    /// report fields this policy (toml) drops or replaces, last_name and
+    /// first_name by default
+    #[clap(long)]
+    pub policy: Option<PathBuf>,
+    /// worker threads, 0 uses every core
+    #[clap(short, long, default_value = "0")]
+    pub jobs: usize,
+    #[clap(flatten)]
+    pub discover: Discover,
 }
 
 #[derive(Debug, Args)]
@@ -83,6 +245,30 @@ pub struct Clean {
     pub file_dir: PathBuf,
     #[clap(short, long, required = true)]
     pub save: PathBuf,
+    /// worker threads, 0 uses every core
+    #[clap(short, long, default_value = "0")]
+    pub jobs: usize,
+    #[clap(flatten)]
+    pub deid: Deid,
+    #[clap(flatten)]
+    pub discover: Discover,
+}
+
+#[derive(Debug, Args)]
+pub struct Reidentify {
+    /// directory of de-identified recordings
+    #[clap(short, long, required = true)]
+    pub file_dir: PathBuf,
+    #[clap(short, long, required = true)]
+    pub save: PathBuf,
+    /// pseudonym map written by clean or split
+    #[clap(short, long, required = true)]
+    pub map: PathBuf,
+    /// worker threads, 0 uses every core
+    #[clap(short, long, default_value = "0")]
+    pub jobs: usize,
+    #[clap(flatten)]
+    pub discover: Discover,
 }
 
 #[derive(Debug, Args)]
@@ -90,8 +276,32 @@ pub struct Diff {
     /// input directory
     #[clap(short, long, required = true)]
     pub file: PathBuf,
+    /// remap table (csv, json or toml), can be multiple
     #[clap(short, long, default_value = "./assets/all.csv")]
-    pub remap_csv: PathBuf,
+    pub remap_csv: Vec<PathBuf>,
+    /// print result as json instead of table
+    #[clap(long)]
+    pub json: bool,
+    /// write draft remap csv for the file's export version
+    #[clap(long)]
+    pub draft: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct RemapCheck {
+    /// remap table (csv, json or toml), can be multiple
+    #[clap(short, long, required = true)]
+    pub remap_csv: Vec<PathBuf>,
+    /// recording to check columns against
+    #[clap(short, long)]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct Provenance {
+    /// split output, exported table or its .provenance.json sidecar
+    #[clap(required = true)]
+    pub file: PathBuf,
 }
 
 fn parse_range_tuple<T, U>(
@@ -103,6 +313,18 @@ where
     U: std::str::FromStr,
     U::Err: std::error::Error + Send + Sync + 'static,
 {
-    let vec = s.split(' ').take(2).collect::<Vec<&str>>();
+    let vec = s.split_whitespace().collect::<Vec<&str>>();
+    if vec.len() != 2 {
+        return Err(format!("'{}' is not a 'start end' pair", s).into());
+    }
     Ok((vec[0].parse()?, vec[1].parse()?))
 }
+
+fn parse_time_range(s: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("'{}' is not a 'start-end' time range", s);
+    let (start, end) = s.trim().split_once('-').ok_or_else(invalid)?;
+    Ok((
+        start.parse().map_err(|_| invalid())?,
+        end.parse().map_err(|_| invalid())?,
+    ))
+}
@@ -1,5 +1,6 @@
 mod args;
-use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
 use args::*;
 
@@ -8,47 +9,207 @@ use gaitool_rs::core::clean::*;
 use gaitool_rs::core::concat::*;
 use gaitool_rs::core::diff::diff_column;
 use gaitool_rs::core::export::*;
+use gaitool_rs::core::normalize::normalizer;
 use gaitool_rs::core::split::*;
+use gaitool_rs::error::Result;
+use gaitool_rs::utils::deid::{DeidPolicy, Deidentifier, PseudonymMap};
+use gaitool_rs::utils::discover::Discovery;
+use gaitool_rs::utils::profile::ColumnProfile;
+use gaitool_rs::utils::provenance::Provenance;
+use gaitool_rs::utils::recording::Posture;
+use gaitool_rs::utils::remap::{validate_remap, RemapTable};
+use gaitool_rs::utils::selection::Selection;
 
 use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::json;
 
 ///
 /// Example command and output
 /// Command:  analyze-rs export -f file/csv/v3.18.44-en-sample.csv -s file/export -r "1 12" -r "15 22"
-/// Response: {"ExportFile":"v3.18.44-en-sample-result.csv"}
+/// Response: {"SpatioTemporal":{"cadence":{..},"LT":{..},"RT":{..},"symmetry":{..}},"ExportFile":"v3.18.44-en-sample-result.csv"}
+///
+/// Command:  analyze-rs export -f file/split/v3.18.44-en-sample.csv -s file/export -t 4.37-15.965
+/// Response: {"SpatioTemporal":{..},"Rejected":[],"ExportFile":"v3.18.44-en-sample-result.csv"}
+///
+/// Command:  analyze-rs export --dir file/split -s file/export
+/// Response: file/split/v3.18.44-en-sample.csv: Success, "v3.18.44-en-sample-result.csv"
+///           {"Succeeded":1,"Failed":0,"UpToDate":0,"CohortFile":"cohort.csv"}
+///
+/// Command:  analyze-rs normalize -f file/split/v3.18.44-en-sample.csv -s file/export
+/// Response: {"Strides":20,"NormalizeFile":"v3.18.44-en-sample-normalized.csv"}
 ///
 /// Command:  analyze-rs concat -f file/export/v3.18.44-en-sample-result.csv -f file/export/v3.18.44-en-sample-result.csv -s file/export
-/// Response: {"ConcatFile":"concat.csv"}
+/// Response: {"ConcatFile":"concat.csv","UpToDate":false}
+///
+/// Command:  analyze-rs provenance file/export/v3.18.44-en-sample-result.csv
+/// Response: {"version":..,"command":"export","params":{..},"strategy":"centred:70","remap":[..],"inputs":[..],"processed_at":..}
 ///
-
 fn main() {
     let args = Cli::parse();
     match args.command {
         Commands::Export(args) => {
-            if let Ok(resp) = exporter(args.file, args.save, args.ranges) {
-                println!("{}", resp)
+            let opts = match load_profile(&args.profile) {
+                Ok(profile) => ExportOptions {
+                    profile,
+                    contact: args.contact,
+                    source: args.events,
+                    reject: args.reject,
+                },
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            if let Some(dir) = args.dir {
+                let pb = progress_bar();
+                let batch = match discovery(&dir, &args.discover, &args.save)
+                    .and_then(|d| {
+                        batch_exporter(
+                            &d,
+                            &args.save,
+                            &opts,
+                            args.jobs,
+                            args.force,
+                            on_done(&pb),
+                        )
+                    }) {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        println!("{}", e);
+                        process::exit(1);
+                    }
+                };
+                pb.finish_and_clear();
+                for file in &batch.skipped {
+                    println!("{}: Up to date", file.display());
+                }
+                for (file, result) in &batch.results {
+                    match result {
+                        Ok(resp) => println!(
+                            "{}: Success, {}",
+                            file.display(),
+                            resp["ExportFile"]
+                        ),
+                        Err(e) => println!("{}: {}", file.display(), e),
+                    }
+                }
+                println!(
+                    "{}",
+                    json!({
+                        "Succeeded": batch.results.len() - batch.failed(),
+                        "Failed": batch.failed(),
+                        "UpToDate": batch.skipped.len(),
+                        "CohortFile": batch.cohort,
+                    })
+                );
+                if batch.failed() > 0 {
+                    process::exit(1);
+                }
+                return;
+            }
+            let selection = if !args.ranges.is_empty() {
+                Selection::Index(args.ranges)
+            } else if !args.time.is_empty() {
+                Selection::Time(args.time)
+            } else {
+                Selection::Header
+            };
+            /* file is required unless dir is given */
+            let file = args.file.unwrap_or_default();
+            match exporter(file, args.save, &selection, &opts) {
+                Ok(resp) => println!("{}", resp),
+                Err(e) => println!("{}", e),
+            };
+        }
+        Commands::Normalize(args) => {
+            match load_profile(&args.profile).and_then(|profile| {
+                let opts = ExportOptions {
+                    profile,
+                    contact: args.contact,
+                    source: args.events,
+                    reject: args.reject,
+                };
+                normalizer(args.file, args.save, args.side, &opts)
+            }) {
+                Ok(resp) => println!("{}", resp),
+                Err(e) => println!("{}", e),
             };
         }
         Commands::Concat(args) => {
-            if let Err(e) = concater(args.file, args.save) {
+            if let Err(e) = load_profile(&args.profile).and_then(|profile| {
+                concater(args.file, args.save, profile.as_ref(), args.force)
+            }) {
                 println!("{}", e)
             };
         }
         Commands::Split(args) => {
-            let paths = fs::read_dir(&args.file_dir).unwrap_or_else(|e| {
-                panic!("Failed to read {:?}. {}", args.file_dir, e)
-            });
-            for file in paths {
-                let file = file.unwrap_or_else(|e| panic!("{}", e)).path();
-                match split(
-                    &file,
-                    &args.save,
-                    args.percent,
-                    &args.remap_csv_dir,
-                    None,
-                ) {
-                    Ok(()) => {
-                        println!("{}: Success", file.display());
+            let profile = match load_profile(&args.profile) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            let deid = match deidentifier(&args.deid) {
+                Ok(deid) => deid,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            let strategy = match parse_strategy(
+                &args.strategy,
+                args.percent,
+                args.min_bout_strides,
+            ) {
+                Ok(strategy) => strategy,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            let opts = SplitOptions {
+                strategy,
+                remap_tables: args.remap,
+                profile,
+                contact: args.contact,
+                source: args.events,
+                reject: args.reject,
+                deid,
+                ..SplitOptions::new(args.percent, &args.remap_csv_dir)
+            };
+            let discovery =
+                match discovery(&args.file_dir, &args.discover, &args.save) {
+                    Ok(discovery) => discovery,
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
+                    }
+                };
+            let pb = progress_bar();
+            let batch = match batch_split(
+                &discovery,
+                &args.save,
+                &opts,
+                args.jobs,
+                args.force,
+                on_done(&pb),
+            ) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            pb.finish_and_clear();
+            for file in &batch.skipped {
+                println!("{}: Up to date", file.display());
+            }
+            for (file, result) in &batch.results {
+                match result {
+                    Ok(remap) => {
+                        println!("{}: Success, {}", file.display(), remap);
                     }
                     Err(e) => {
                         println!("{}: {}", file.display(), e)
@@ -57,19 +218,130 @@ fn main() {
             }
         }
         Commands::Check(args) => {
-            if let Err(e) = check(args.file_dir) {
+            let root = &args.file_dir;
+            let pb = progress_bar();
+            if let Err(e) = load_policy(&args.policy).and_then(|policy| {
+                let d = discovery(root, &args.discover, root)?;
+                check(&d, args.jobs, &policy, on_done(&pb))
+            }) {
                 println!("{}", e)
             };
+            pb.finish_and_clear();
         }
         Commands::Clean(args) => {
-            if let Err(e) = clean(args.file_dir, args.save) {
+            let pb = progress_bar();
+            if let Err(e) = deidentifier(&args.deid).and_then(|deid| {
+                let d = discovery(&args.file_dir, &args.discover, &args.save)?;
+                clean(&d, args.save, args.jobs, &deid, on_done(&pb))
+            }) {
+                println!("{}", e)
+            };
+            pb.finish_and_clear();
+        }
+        Commands::Reidentify(args) => {
+            if let Err(e) = PseudonymMap::load(&args.map).and_then(|map| {
+                let d = discovery(&args.file_dir, &args.discover, &args.save)?;
+                reidentify(&d, args.save, args.jobs, &map)
+            }) {
                 println!("{}", e)
             };
         }
         Commands::Diff(args) => {
-            if let Err(e) = diff_column(&args.file, &args.remap_csv) {
-                println!("{}", e);
+            let diff = match diff_column(&args.file, &args.remap_csv) {
+                Ok(diff) => diff,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+            if args.json {
+                println!("{}", diff.to_json());
+            } else {
+                println!("{}", diff);
+            }
+            if let Some(draft) = args.draft {
+                if let Err(e) = RemapTable::merge(&args.remap_csv)
+                    .and_then(|table| diff.write_draft(&table, &draft))
+                {
+                    println!("{}", e);
+                }
             }
         }
+        Commands::RemapCheck(args) => {
+            match validate_remap(&args.remap_csv, args.file.as_deref()) {
+                Ok(report) => print!("{}", report),
+                Err(e) => println!("{}", e),
+            }
+        }
+        Commands::Provenance(args) => match Provenance::read(&args.file) {
+            Ok(provenance) => println!(
+                "{}",
+                serde_json::to_string_pretty(&provenance.to_json())
+                    .expect("json value is always serializable")
+            ),
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        },
+    }
+}
+
+/// input files under `root` by discover args, `save` is never walked
+fn discovery(root: &Path, args: &Discover, save: &Path) -> Result<Discovery> {
+    let mut discovery = Discovery::new(root).skip(save);
+    if args.flat {
+        discovery = discovery.flat();
+    }
+    for pattern in &args.include {
+        discovery = discovery.include(pattern)?;
+    }
+    for pattern in &args.exclude {
+        discovery = discovery.exclude(pattern)?;
+    }
+    for subject in &args.subject {
+        discovery = discovery.subject(subject);
+    }
+    for posture in &args.posture {
+        discovery = discovery.posture(Posture::from(posture.as_str()));
     }
+    for order in &args.order {
+        discovery = discovery.order(order);
+    }
+    Ok(discovery)
+}
+
+/// bar over files of a batch command, length is set by `on_done`
+fn progress_bar() -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{prefix:.bold.dim} {wide_bar} {pos}/{len} {msg}"),
+    );
+    pb
+}
+
+/// batch callback moving `pb` to files done
+fn on_done(pb: &ProgressBar) -> impl Fn(&Path, usize, usize) + Sync + '_ {
+    move |file, done, total| {
+        pb.set_length(total as u64);
+        pb.set_position(done as u64);
+        pb.set_message(format!("Processed {}", file.display()));
+    }
+}
+
+/// policy and pseudonym map of deid args
+fn deidentifier(args: &Deid) -> Result<Deidentifier> {
+    Deidentifier::new(load_policy(&args.policy)?, args.map.as_deref())
+}
+
+fn load_policy(path: &Option<PathBuf>) -> Result<DeidPolicy> {
+    match path {
+        Some(path) => DeidPolicy::load(path),
+        None => Ok(DeidPolicy::default()),
+    }
+}
+
+fn load_profile(path: &Option<PathBuf>) -> Result<Option<ColumnProfile>> {
+    path.as_ref().map(ColumnProfile::load).transpose()
 }
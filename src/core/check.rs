@@ -1,68 +1,80 @@
-use crate::utils::util::*;
+use crate::error::Result;
+use crate::utils::batch::par_map_progress;
+use crate::utils::deid::DeidPolicy;
+use crate::utils::discover::Discovery;
+use crate::utils::header::RecordingHeader;
+use crate::utils::recording::{Posture, RecordingId};
 
-use polars::prelude::*;
 use std::collections::HashMap;
-use std::fs::{self, create_dir_all, remove_dir_all};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn check(file_dir: PathBuf) -> Result<()> {
-    let save_dir = "./tmp/";
-    create_dir_all(save_dir)?;
-    let paths = fs::read_dir(&file_dir)?;
-    let mut cnt = HashMap::new();
-    for file in paths {
-        let file = file?;
-        let file = file.path();
-        let file = file.display().to_string();
-        /* read file */
-        let filename = get_file_name(&file);
-        let file_stem = get_file_stem(&file);
-        let saved_path = join_path(&save_dir, &filename.as_str());
-
-        let parts = file_stem.split("_").collect::<Vec<&str>>();
-        if parts.len() != 2 {
-            println!("Can't parse file name: {}. Skipped!", filename);
-            continue;
-        }
-        // {record_datetime}_{user_id}-{assistant_user_id}-{location}-{posture_id}-[{reason_id},{reason_id}]-{order}.csv
-        let mut name_vec = parts[0].split("-").collect::<Vec<&str>>();
-        name_vec.append(&mut parts[1].split("-").collect::<Vec<&str>>());
+/// report header fields `policy` removes but still there and missing
+/// selection of every discovered recording, headers are read on `jobs`
+/// threads, 0 uses every core, `on_done` is called with each read file,
+/// files done and files to read
+pub fn check<P>(
+    discovery: &Discovery,
+    jobs: usize,
+    policy: &DeidPolicy,
+    on_done: P,
+) -> Result<()>
+where
+    P: Fn(&Path, usize, usize) + Sync + Send,
+{
+    let files = discovery
+        .find()?
+        .into_iter()
+        .map(|found| found.path)
+        .collect::<Vec<PathBuf>>();
+    let headers = par_map_progress(
+        &files,
+        jobs,
+        |file| {
+            /* read only header */
+            RecordingId::from_path(file)
+                .map(|id| (id, RecordingHeader::read(file)))
+        },
+        |file, _, done| on_done(file, done, files.len()),
+    );
 
-        if name_vec.len() < 11 {
-            println!("Can't parse file name: {}. Skipped!", filename);
-            continue;
-        }
-        if name_vec[7] == "1" {
-            let count = cnt.entry(name_vec[5].to_string() + "-1").or_insert(0);
-            *count += 1;
-        } else if name_vec[7] == "2" {
-            let count = cnt.entry(name_vec[5].to_string() + "-2").or_insert(0);
+    /* report in file order */
+    let mut cnt = HashMap::new();
+    for (file, result) in files.iter().zip(headers) {
+        let (id, header) = match result {
+            Ok(r) => r,
+            Err(e) => {
+                println!("{}. Skipped!", e);
+                continue;
+            }
+        };
+        if let Posture::Walking | Posture::Treadmill = id.posture {
+            let count = cnt
+                .entry(format!("{}-{}", id.subject_id, id.posture))
+                .or_insert(0);
             *count += 1;
         }
 
-        /* read/write only header */
-        extract_header(&file, &saved_path);
-        /* header to dataframe */
-        let header_df = CsvReader::from_path(&saved_path)?
-            .with_ignore_parser_errors(true)
-            .finish()?;
-        /* check last_name first_name selection */
-        let mut checks = vec![];
-        if let Ok(_) = header_df.column("last_name") {
-            checks.push("found: last_name");
-        }
-        if let Ok(_) = header_df.column("first_name") {
-            checks.push("found: first_name");
-        }
-        if let Err(_) = header_df.column("selection") {
-            checks.push("not found: selection");
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        /* check identifying fields and selection */
+        let mut checks = policy
+            .leftovers(&header)
+            .iter()
+            .map(|field| format!("found: {}", field))
+            .collect::<Vec<String>>();
+        if header.selection.is_none() {
+            checks.push("not found: selection".to_string());
         }
         if !checks.is_empty() {
-            println!("{:<50 } {:?}", &file, checks);
+            println!("{:<50 } {:?}", file.display(), checks);
         }
     }
 
-    remove_dir_all(save_dir)?;
     println!("{:#?}", cnt);
 
     Ok(())
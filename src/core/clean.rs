@@ -1,63 +1,109 @@
-use crate::utils::util::*;
+use crate::error::{GaitError, Result};
+use crate::utils::batch::{par_map, par_map_progress};
+use crate::utils::deid::{Deidentifier, PseudonymMap};
+use crate::utils::discover::Discovery;
+use crate::utils::noraxon::{NoraxonReader, NoraxonWriter, Recording};
+use crate::utils::recording::RecordingId;
 
-use indicatif::{ProgressBar, ProgressStyle};
-use polars::prelude::*;
-use std::fs::{self, create_dir_all};
+use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 
-pub fn clean(file_dir: PathBuf, save_dir: PathBuf) -> Result<()> {
-    create_dir_all(&save_dir)?;
-    let paths = fs::read_dir(&file_dir)?;
-    let file_num = fs::read_dir(&file_dir)?.count();
-    let spinner_style = ProgressStyle::default_spinner()
-        .template("{prefix:.bold.dim} {spinner} {wide_msg}")
-        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
-
-    let pb = ProgressBar::new(file_num as u64);
-    for file in paths {
-        let file = file?;
-        let file = file.path();
-        let file = file.display().to_string();
-        /* read file */
-        let filename = get_file_name(Path::new(&file));
-        let file_stem = get_file_stem(Path::new(&file));
-        let saved_path = join_path(Path::new(&save_dir), Path::new(&filename));
-        pb.set_style(spinner_style.clone());
-        pb.set_message(format!("Processing {}", filename));
-
-        let parts = file_stem.split("_").collect::<Vec<&str>>();
-        if parts.len() != 2 {
-            pb.set_message(format!(
-                "Can't parse file name: {}. Skipped!",
-                filename
-            ));
-            continue;
-        }
+/// de-identify every discovered recording by `deid` policy into the same
+/// sub-tree of `save_dir`, files are cleaned on `jobs` threads, 0 uses
+/// every core, pseudonyms handed out are saved to its map, `on_done` is
+/// called with each cleaned file, files done and files to clean
+pub fn clean<P>(
+    discovery: &Discovery,
+    save_dir: PathBuf,
+    jobs: usize,
+    deid: &Deidentifier,
+    on_done: P,
+) -> Result<()>
+where
+    P: Fn(&Path, usize, usize) + Sync + Send,
+{
+    create_dir_all(&save_dir).map_err(GaitError::io(&save_dir))?;
+    deid.ensure_outside(&save_dir)?;
+    let files = discovery.find()?;
 
-        let mut name_vec = parts[0].split("-").collect::<Vec<&str>>();
-        name_vec.append(&mut parts[1].split("-").collect::<Vec<&str>>());
+    let results = par_map_progress(
+        &files,
+        jobs,
+        |found| {
+            let file = &found.path;
+            RecordingId::from_path(file)?;
+            clean_file(file, &found.mirror(&save_dir), deid)
+        },
+        |found, _, done| on_done(&found.path, done, files.len()),
+    );
+    deid.save()?;
 
-        if name_vec.len() < 11 {
-            pb.set_message(format!(
-                "Can't parse file name: {}. Skipped!",
-                filename
-            ));
-            continue;
+    /* report in file order */
+    for (found, result) in files.iter().zip(results) {
+        match result {
+            Ok(()) => {}
+            Err(e @ GaitError::FileName { .. }) => {
+                println!("{}. Skipped!", e)
+            }
+            Err(e) => println!("{}: {}", found.path.display(), e),
         }
-        pb.inc(1);
-
-        /* read/write only header */
-        extract_header(&file, &saved_path);
-        /* header to dataframe */
-        let mut header_df = CsvReader::from_path(&saved_path)?.finish()?;
-        header_df = header_df
-            .lazy()
-            .drop_columns(["last_name", "first_name"])
-            .collect()?;
-        save_csv(&mut header_df, &save_dir.display().to_string(), &filename);
-        let mut df = CsvReader::from_path(&file)?.with_skip_rows(3).finish()?;
-        append_df2header(&mut df, &save_dir.display().to_string(), &filename);
     }
 
     Ok(())
 }
+
+fn clean_file(file: &Path, save_dir: &Path, deid: &Deidentifier) -> Result<()> {
+    create_dir_all(save_dir).map_err(GaitError::io(save_dir))?;
+    let Recording {
+        mut header,
+        mut data,
+        ..
+    } = NoraxonReader::new(file).finish()?;
+    deid.header(&mut header)?;
+    NoraxonWriter::new(save_dir.join(deid.file_name(file)?))
+        .finish(&header, &mut data)
+}
+
+/// restore original names and hashed header fields of every discovered
+/// recording de-identified with `map` into the same sub-tree of
+/// `save_dir`, dropped and replaced fields are gone for good
+pub fn reidentify(
+    discovery: &Discovery,
+    save_dir: PathBuf,
+    jobs: usize,
+    map: &PseudonymMap,
+) -> Result<()> {
+    create_dir_all(&save_dir).map_err(GaitError::io(&save_dir))?;
+    let files = discovery.find()?;
+    let results = par_map(&files, jobs, |found| {
+        let id = map.original_id(&RecordingId::from_path(&found.path)?);
+        let save_dir = found.mirror(&save_dir);
+        create_dir_all(&save_dir).map_err(GaitError::io(&save_dir))?;
+        let Recording {
+            mut header,
+            mut data,
+            ..
+        } = NoraxonReader::new(&found.path).finish()?;
+        let restored = map.restore_header(&mut header);
+        NoraxonWriter::new(save_dir.join(id.file_name()))
+            .finish(&header, &mut data)?;
+        Ok((id, restored))
+    });
+
+    /* report in file order */
+    for (found, result) in files.iter().zip(results) {
+        match result {
+            Ok((id, restored)) => println!(
+                "{}: {} {:?}",
+                found.path.display(),
+                id.file_name(),
+                restored
+            ),
+            Err(e @ GaitError::FileName { .. }) => {
+                println!("{}. Skipped!", e)
+            }
+            Err(e) => println!("{}: {}", found.path.display(), e),
+        }
+    }
+    Ok(())
+}
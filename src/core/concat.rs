@@ -1,41 +1,86 @@
 use polars::prelude::*;
 use serde_json::json;
 use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::error::{GaitError, Result};
+use crate::utils::manifest::{Manifest, Stamp};
+use crate::utils::profile::ColumnProfile;
+use crate::utils::provenance::Provenance;
 use crate::utils::util::*;
 
-pub fn concater(files: Vec<PathBuf>, save_dir: PathBuf) -> Result<()> {
-    create_dir_all(&save_dir)?;
+/// output of concat
+pub const CONCAT_FILE: &str = "concat.csv";
+
+/// concat files into `CONCAT_FILE`, skipped if inputs and profile are the
+/// same as last time unless `force`
+pub fn concater(
+    files: Vec<PathBuf>,
+    save_dir: PathBuf,
+    profile: Option<&ColumnProfile>,
+    force: bool,
+) -> Result<()> {
+    let params = json!({ "profile": profile.map(|p| &p.columns) });
+    let mut manifest = Manifest::open(&save_dir, "concat", params);
+    let stamps = files
+        .iter()
+        .map(|f| Ok((f.display().to_string(), Stamp::of(f)?)))
+        .collect::<Result<Vec<(String, Stamp)>>>()?;
+    let stamp = Stamp::combine(&stamps);
+    let up_to_date =
+        !force && manifest.is_fresh(&save_dir, CONCAT_FILE, &stamp);
+    if !up_to_date {
+        let file = concat_files(&files, &save_dir, CONCAT_FILE, profile)?;
+        manifest.record(CONCAT_FILE, stamp, vec![file]);
+        manifest.save(&save_dir)?;
+    }
+
+    let resp_filter_api = json!({
+        "ConcatFile": CONCAT_FILE,
+        "UpToDate": up_to_date,
+    })
+    .to_string();
+    println!("{}", resp_filter_api);
+    Ok(())
+}
+
+/// concat csv files row-wise into `save_dir/name`, return file name
+pub fn concat_files(
+    files: &[PathBuf],
+    save_dir: &Path,
+    name: &str,
+    profile: Option<&ColumnProfile>,
+) -> Result<String> {
+    create_dir_all(save_dir).map_err(GaitError::io(save_dir))?;
 
     /* read all files ioto LazyFrame */
-    let ldfs =
-        files
-            .iter()
-            .fold(Vec::with_capacity(files.len()), |mut v, file| {
-                v.push(
-                    LazyCsvReader::new(file.as_path().display().to_string())
-                        .finish()
-                        .expect(
-                            format!(
-                                "Read {} failed",
-                                file.as_path().display().to_string()
-                            )
-                            .as_str(),
-                        ),
-                );
-                v
-            });
+    let ldfs = files
+        .iter()
+        .map(|file| {
+            if !file.is_file() {
+                return Err(GaitError::not_found(file));
+            }
+            Ok(LazyCsvReader::new(file.display().to_string()).finish()?)
+        })
+        .collect::<Result<Vec<LazyFrame>>>()?;
 
     /* concat all */
     let mut concat_df = concat(ldfs, true)?.collect()?;
+    if let Some(profile) = profile {
+        concat_df = profile.apply_results(&concat_df)?;
+    }
+    let saved =
+        save_csv(&mut concat_df, &save_dir.display().to_string(), name)?
+            .to_string();
 
-    let resp_filter_api = json!({"ConcatFile": save_csv(
-        &mut concat_df,
-        &save_dir.display().to_string(),
-        "concat.csv",
-    )})
-    .to_string();
-    println!("{}", resp_filter_api);
-    Ok(())
+    /* inputs inside save dir, e.g. cohort members, by relative path */
+    let params = json!({ "profile": profile.map(|p| &p.columns) });
+    files
+        .iter()
+        .try_fold(Provenance::new("concat", params), |p, f| {
+            let name = f.strip_prefix(save_dir).unwrap_or(f);
+            p.input(&name.display().to_string(), f)
+        })?
+        .write_sidecar(&save_dir.join(&saved))?;
+    Ok(saved)
 }
@@ -1,61 +1,195 @@
-use polars::prelude::*;
-use similar::{ChangeTag, TextDiff};
-use std::path::PathBuf;
-
-use crate::utils::util::get_keys;
-
-pub fn diff_column(file: &PathBuf, remap_csv: &PathBuf) -> Result<Vec<String>> {
-    let (ori_key, _) = get_keys(remap_csv.to_str().unwrap())
-        .unwrap_or_else(|e| panic!("{:?} {}", remap_csv, e));
-    let source = ori_key.join("\n");
-    let df = CsvReader::from_path(file)?.with_skip_rows(3).finish()?;
-    let col = df.get_column_names();
-    let target = col.join("\n");
-
-    let diff = TextDiff::from_lines(&source, &target);
-
-    let mut result = vec![];
-    for (_idx, group) in diff.grouped_ops(3).iter().enumerate() {
-        for op in group {
-            for change in diff.iter_inline_changes(op) {
-                let sign = match change.tag() {
-                    ChangeTag::Delete => "-",
-                    ChangeTag::Insert => "+",
-                    ChangeTag::Equal => " ",
-                };
-                if change.old_index() == change.new_index() {
-                    continue;
-                }
-                let mut line = String::new();
-                line.push_str(&format!(
-                    "{} | {: <4} {: <4} | ",
-                    &sign,
-                    if let Some(i) = change.old_index() {
-                        i.to_string()
-                    } else {
-                        " ".to_string()
-                    },
-                    if let Some(i) = change.new_index() {
-                        i.to_string()
-                    } else {
-                        " ".to_string()
-                    },
-                ));
-                let text = change.iter_strings_lossy().fold(
-                    String::new(),
-                    |mut v, (_, s)| {
-                        v.push_str(&s);
-                        v
-                    },
-                );
-                line.push_str(&text);
-                if change.missing_newline() {
-                    line.push_str("\n");
+use serde_json::{json, Value};
+use similar::TextDiff;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::{GaitError, Result};
+use crate::utils::noraxon::NoraxonReader;
+use crate::utils::remap::RemapTable;
+
+/// prefix shared by Noraxon segment columns
+const SEGMENT_PREFIX: &str = "Noraxon MyoMotion-Segments-";
+/// lowest score to pair a missing and an extra column as rename
+const RENAME_THRESHOLD: f32 = 0.6;
+
+/// missing table column which probably became another file column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub original: String,
+    pub column: String,
+    pub score: f32,
+}
+
+/// table `Original` keys compared with file columns
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnDiff {
+    /// in both table and file
+    pub matched: Vec<String>,
+    /// in table only
+    pub missing: Vec<String>,
+    /// in file only
+    pub extra: Vec<String>,
+    pub renames: Vec<Rename>,
+    /// file columns in file order, for draft table
+    columns: Vec<String>,
+}
+
+pub fn diff_column(file: &Path, remap_csv: &[PathBuf]) -> Result<ColumnDiff> {
+    let table = RemapTable::merge(remap_csv)?;
+    let df = NoraxonReader::new(file).finish()?.data;
+    let columns = df
+        .get_column_names()
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>();
+    Ok(ColumnDiff::new(&table.originals(), &columns))
+}
+
+impl ColumnDiff {
+    pub fn new(originals: &[String], columns: &[String]) -> Self {
+        let matched = originals
+            .iter()
+            .filter(|o| columns.contains(o))
+            .cloned()
+            .collect::<Vec<String>>();
+        let mut missing = originals
+            .iter()
+            .filter(|o| !columns.contains(o))
+            .cloned()
+            .collect::<Vec<String>>();
+        let mut extra = columns
+            .iter()
+            .filter(|c| !originals.contains(c))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        /* pair best scored missing/extra first, each column used once */
+        let mut candidates = vec![];
+        for original in missing.iter() {
+            for column in extra.iter() {
+                let score = rename_score(original, column);
+                if score >= RENAME_THRESHOLD {
+                    candidates.push(Rename {
+                        original: original.clone(),
+                        column: column.clone(),
+                        score,
+                    });
                 }
-                result.push(line);
             }
         }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut renames: Vec<Rename> = vec![];
+        for c in candidates {
+            if renames
+                .iter()
+                .all(|r| r.original != c.original && r.column != c.column)
+            {
+                renames.push(c);
+            }
+        }
+        missing.retain(|o| renames.iter().all(|r| &r.original != o));
+        extra.retain(|c| renames.iter().all(|r| &r.column != c));
+
+        Self {
+            matched,
+            missing,
+            extra,
+            renames,
+            columns: columns.to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "matched": self.matched,
+            "missing": self.missing,
+            "extra": self.extra,
+            "renames": self.renames.iter().map(|r| json!({
+                "original": r.original,
+                "column": r.column,
+                "score": r.score,
+            })).collect::<Vec<Value>>(),
+        })
+    }
+
+    /// remap table for the file's export version, columns are mapped
+    /// through matched or renamed originals, extra columns keep own name
+    pub fn write_draft(&self, table: &RemapTable, path: &Path) -> Result<()> {
+        let mut wtr = csv::WriterBuilder::new()
+            .quote_style(csv::QuoteStyle::Always)
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_path(path)
+            .map_err(|e| GaitError::io(path)(e.into()))?;
+        let write_err = |e: csv::Error| GaitError::io(path)(e.into());
+        wtr.write_record(["Original", "New"]).map_err(write_err)?;
+        for column in self.columns.iter() {
+            let original = self
+                .renames
+                .iter()
+                .find(|r| &r.column == column)
+                .map_or(column.as_str(), |r| r.original.as_str());
+            let new = table.get(original).unwrap_or(column);
+            wtr.write_record([column, new]).map_err(write_err)?;
+        }
+        wtr.flush().map_err(GaitError::io(path))
+    }
+}
+
+impl fmt::Display for ColumnDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .matched
+            .iter()
+            .chain(self.missing.iter())
+            .chain(self.renames.iter().map(|r| &r.original))
+            .map(|s| s.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("original".len());
+        let row = |f: &mut fmt::Formatter<'_>, s: &str, o: &str, c: &str| {
+            let pad = width - o.chars().count();
+            writeln!(f, "{: <8} | {}{} | {}", s, o, " ".repeat(pad), c)
+        };
+        row(f, "status", "original", "column")?;
+        for m in self.matched.iter() {
+            row(f, "matched", m, m)?;
+        }
+        for r in self.renames.iter() {
+            let column = format!("{} ({:.2})", r.column, r.score);
+            row(f, "rename", &r.original, &column)?;
+        }
+        for m in self.missing.iter() {
+            row(f, "missing", m, "")?;
+        }
+        for e in self.extra.iter() {
+            row(f, "extra", "", e)?;
+        }
+        write!(
+            f,
+            "matched: {}, rename: {}, missing: {}, extra: {}",
+            self.matched.len(),
+            self.renames.len(),
+            self.missing.len(),
+            self.extra.len()
+        )
     }
+}
 
-    Ok(result)
+/// split `Noraxon MyoMotion-Segments-<part>-<signal>` into part and signal
+fn segment(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix(SEGMENT_PREFIX)?.split_once('-')
+}
+
+/// similarity of two names in [0, 1], segment columns are compared by
+/// part and signal so a renamed part with same signal still pairs up,
+/// segment and non-segment columns never pair
+fn rename_score(a: &str, b: &str) -> f32 {
+    let ratio = |a: &str, b: &str| TextDiff::from_chars(a, b).ratio();
+    match (segment(a), segment(b)) {
+        (Some((part_a, signal_a)), Some((part_b, signal_b))) => {
+            (ratio(part_a, part_b) + 2. * ratio(signal_a, signal_b)) / 3.
+        }
+        (None, None) => ratio(a, b),
+        _ => 0.,
+    }
 }
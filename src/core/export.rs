@@ -1,126 +1,410 @@
 use polars::functions::hor_concat_df;
 use polars::prelude::*;
-use serde_json::Value;
 use serde_json::json;
+use serde_json::Value;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
 
+use crate::core::concat::concat_files;
+use crate::error::{GaitError, Result};
+use crate::utils::batch::{par_map, par_map_progress};
+use crate::utils::contact::ContactPolicy;
+use crate::utils::discover::{Discovery, Found};
+use crate::utils::events::{ensure_contact, ContactSource};
+use crate::utils::gait::{phases_df, GaitEvents, Phase, Side};
+use crate::utils::header::{
+    format_selection, RecordingHeader, SELECTION_KEY, STRATEGY_KEY,
+};
+use crate::utils::manifest::{Manifest, Stamp};
+use crate::utils::noraxon::NoraxonReader;
 use crate::utils::preprocess::*;
+use crate::utils::profile::ColumnProfile;
+use crate::utils::provenance::Provenance;
+use crate::utils::quality::*;
+use crate::utils::selection::Selection;
+use crate::utils::spatiotemporal::SpatioTemporal;
 use crate::utils::util::*;
 
+/// settings of export besides input file and ranges
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// keep only profile columns in max/min
+    pub profile: Option<ColumnProfile>,
+    pub contact: ContactPolicy,
+    /// contact sensor or IMU detected contact
+    pub source: ContactSource,
+    /// strides dropped besides the ones rejected in header
+    pub reject: RejectPolicy,
+}
+
+impl ExportOptions {
+    /// settings that change export output
+    pub fn params(&self) -> Value {
+        json!({
+            "profile": self.profile.as_ref().map(|p| &p.columns),
+            "contact": self.contact.to_string(),
+            "events": self.source.to_string(),
+            "reject": self.reject.to_string(),
+        })
+    }
+}
+
+/// cohort table of batch export
+pub const COHORT_FILE: &str = "cohort.csv";
+
+/// file name suffix of an export result
+pub const RESULT_SUFFIX: &str = "-result.csv";
+
+/// outcome of `batch_exporter`
+#[derive(Debug)]
+pub struct BatchExport {
+    /// response or error of every exported file, in path order
+    pub results: Vec<(PathBuf, Result<Value>)>,
+    /// files exported before with same settings and unchanged since
+    pub skipped: Vec<PathBuf>,
+    /// results of succeeded files concatenated, none if all failed
+    pub cohort: Option<String>,
+}
+
+impl BatchExport {
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|(_, r)| r.is_err()).count()
+    }
+}
+
+/// export every discovered csv with its own `selection` header into the
+/// same sub-tree of `save_dir` on `jobs` threads (0 uses every core),
+/// then concat result rows into `COHORT_FILE`, files and cohort already
+/// up to date are skipped unless `force`, outputs in `save_dir` are never
+/// taken as input even when it is or lies inside the input dir, `on_done`
+/// is called with each exported file, files done and files to export
+pub fn batch_exporter<P>(
+    discovery: &Discovery,
+    save_dir: &Path,
+    opts: &ExportOptions,
+    jobs: usize,
+    force: bool,
+    on_done: P,
+) -> Result<BatchExport>
+where
+    P: Fn(&Path, usize, usize) + Sync + Send,
+{
+    let files = discovery
+        .clone()
+        .skip(save_dir)
+        .exclude(&format!("**/*{}", RESULT_SUFFIX))?
+        .exclude(&format!("**/{}", COHORT_FILE))?
+        .find()?
+        .into_iter()
+        .filter(|f| f.path.extension().is_some_and(|e| e == "csv"))
+        .collect::<Vec<Found>>();
+    let mut manifest = Manifest::open(save_dir, "export", opts.params());
+    let checks = par_map(&files, jobs, |found| {
+        manifest.check(save_dir, &found.key(), &found.path)
+    });
+    let mut todo = vec![];
+    let mut skipped = vec![];
+    for (found, check) in files.iter().zip(checks) {
+        match check? {
+            (true, _) if !force => skipped.push(found.path.clone()),
+            (_, stamp) => todo.push((found, stamp)),
+        }
+    }
+
+    let responses = par_map_progress(
+        &todo,
+        jobs,
+        |(found, _)| export_found(found, save_dir, opts),
+        |(found, _), _, done| on_done(&found.path, done, todo.len()),
+    );
+    for ((found, stamp), resp) in todo.iter().zip(&responses) {
+        match resp {
+            Ok(resp) => {
+                let export_file =
+                    resp["ExportFile"].as_str().unwrap_or_default();
+                manifest.record(
+                    &found.key(),
+                    stamp.clone(),
+                    vec![export_file.to_string()],
+                );
+            }
+            Err(_) => manifest.remove(&found.key()),
+        }
+    }
+
+    /* cohort of every exported file, rebuilt when one of them changed */
+    let members = files
+        .iter()
+        .filter_map(|f| {
+            Some((f.key(), manifest.entries.get(&f.key())?.clone()))
+        })
+        .collect::<Vec<_>>();
+    let stamp = Stamp::combine(
+        &members
+            .iter()
+            .map(|(key, e)| (key.clone(), e.stamp.clone()))
+            .collect::<Vec<_>>(),
+    );
+    let cohort = if members.is_empty() {
+        manifest.remove(COHORT_FILE);
+        None
+    } else if !force && manifest.is_fresh(save_dir, COHORT_FILE, &stamp) {
+        Some(COHORT_FILE.to_string())
+    } else {
+        let exported = members
+            .iter()
+            .flat_map(|(_, e)| e.outputs.iter().map(|o| save_dir.join(o)))
+            .collect::<Vec<PathBuf>>();
+        let cohort = concat_files(&exported, save_dir, COHORT_FILE, None)?;
+        manifest.record(COHORT_FILE, stamp, vec![cohort.clone()]);
+        Some(cohort)
+    };
+    manifest.save(save_dir)?;
+
+    let results = todo
+        .into_iter()
+        .map(|(found, _)| found.path.clone())
+        .zip(responses)
+        .collect();
+    Ok(BatchExport {
+        results,
+        skipped,
+        cohort,
+    })
+}
+
+/// export `found` with its `selection` header, result file relative to
+/// `save_dir`
+fn export_found(
+    found: &Found,
+    save_dir: &Path,
+    opts: &ExportOptions,
+) -> Result<Value> {
+    let mut resp = exporter(
+        found.path.clone(),
+        found.mirror(save_dir),
+        &Selection::Header,
+        opts,
+    )?;
+    let export_file = found
+        .relative
+        .join(resp["ExportFile"].as_str().unwrap_or_default());
+    resp["ExportFile"] = json!(export_file.display().to_string());
+    Ok(resp)
+}
+
+/// summarise strides in `selection` of file into one result row
 pub fn exporter(
     file: PathBuf,
     save_dir: PathBuf,
-    ranges: Vec<(u32, u32)>,
+    selection: &Selection,
+    opts: &ExportOptions,
 ) -> Result<Value> {
-    create_dir_all(&save_dir)?;
+    create_dir_all(&save_dir).map_err(GaitError::io(&save_dir))?;
     /* extract file name */
-    let filename = file
-        .file_name()
-        .expect("Err get input file name")
-        .to_str()
-        .unwrap()
-        .to_string();
+    let filename = get_file_name(&file)?;
     /* file name without suffix */
-    let outfile = Path::new(&filename)
-        .file_stem()
-        .expect("Err get input file stem")
-        .to_str()
-        .unwrap()
-        .to_string();
+    let outfile = get_file_stem(&file)?;
 
-    let mut df = CsvReader::from_path(file)?.finish()?;
+    let recording = NoraxonReader::new(&file).finish()?;
+    let df = ensure_contact(recording.data, opts.source)
+        .map_err(|e| e.in_file(&file))?;
+    let events = remap_contact(df.clone(), &opts.contact)
+        .and_then(|sup_df| GaitEvents::from_contact(&sup_df))
+        .map_err(|e| e.in_file(&file))?;
+    let strides = events.gait().map_err(|e| e.in_file(&file))?;
+    let rejected = rejected_strides(
+        &df,
+        &events,
+        &strides,
+        &opts.reject,
+        recording.header.get(REJECTED_KEY),
+    )
+    .map_err(|e| e.in_file(&file))?;
+    let header = match (selection, &recording.header.selection) {
+        (Selection::Header, None) => {
+            return Err(GaitError::MissingHeaderField {
+                path: file,
+                field: SELECTION_KEY.to_string(),
+            })
+        }
+        (_, header) => header.as_deref().unwrap_or(&[]),
+    };
+    let ranges = selection.resolve(&strides, header)?;
 
-    let gait_df = cal_gait(&df)?;
+    let gait_df = phases_df(&strides)?;
     /* calculate every gap */
-    let gait_ldf = gait_df
-        .clone()
-        .lazy()
-        .with_column((col("end") - col("start")).alias("gait mean"));
-    let ls_ldf = cal_x_support(&df, L_SG_SUP)?
-        .lazy()
-        .with_column((col("end") - col("start")).alias("ls mean"));
-    let rs_ldf = cal_x_support(&df, R_SG_SUP)?
-        .lazy()
-        .with_column((col("end") - col("start")).alias("rs mean"));
-    let db_ldf = cal_x_support(&df, DB_SUP)?
-        .lazy()
-        .with_column((col("end") - col("start")).alias("db mean"));
-    df = df
-        .lazy()
-        .drop_columns([DB_SUP, L_SG_SUP, R_SG_SUP, SG_SUP])
-        .collect()?;
+    let gait_ldf = phase_ldf(&gait_df, "gait mean");
+    let ls_ldf = support_ldf(&events.single_support(Side::Left), "ls mean")
+        .map_err(|e| e.in_file(&file))?;
+    let rs_ldf = support_ldf(&events.single_support(Side::Right), "rs mean")
+        .map_err(|e| e.in_file(&file))?;
+    let db_ldf = support_ldf(&events.double_supports(), "db mean")
+        .map_err(|e| e.in_file(&file))?;
 
     let mut vec_ranges: Vec<(f64, f64)> = vec![]; // for calculate valid data
     let mut str_ranges: Vec<String> = vec![]; // for output selection
+    let mut time_ranges: Vec<(f64, f64)> = vec![]; // for spatiotemporal
     let mut gait_ldfs = vec![];
     let mut ls_ldfs = vec![];
     let mut rs_ldfs = vec![];
     let mut db_ldfs = vec![];
-    for (start, end) in ranges {
-        let gait_slice = gait_df.slice(start as i64, (end - start) as usize);
-        gait_slice["start"]
-            .f64()?
-            .into_iter()
-            .zip(gait_slice["end"].f64()?.into_iter())
-            .for_each(|(s, e)| {
-                vec_ranges.push((s.unwrap(), e.unwrap()));
-            });
-
-        let t_start = gait_df["start"].f64()?.get(start as usize).unwrap();
-        let t_end = gait_df["start"].f64()?.get(end as usize).unwrap();
+    for &(t_start, t_end) in &ranges {
+        strides
+            .iter()
+            .filter(|s| t_start <= s.start && s.start < t_end)
+            .filter(|s| rejected.iter().all(|r| !r.contains(s.start)))
+            .for_each(|s| vec_ranges.push((s.start, s.end)));
+
         str_ranges.push(format!("{}-{}", t_start, t_end));
-        /* add valid ranges in gait/ls/rs/db between time start/end */
-        let expr = col("start")
-            .gt_eq(lit(t_start))
-            .and(col("start").lt(lit(t_end)));
-        let sel_col = &[col("^.*mean$")];
-        gait_ldfs.push(gait_ldf.clone().filter(expr.clone()).select(sel_col));
-        ls_ldfs.push(ls_ldf.clone().filter(expr.clone()).select(sel_col));
-        rs_ldfs.push(rs_ldf.clone().filter(expr.clone()).select(sel_col));
-        db_ldfs.push(db_ldf.clone().filter(expr.clone()).select(sel_col));
+        /* add valid ranges in gait/ls/rs/db between time start/end,
+        rejected strides are cut out */
+        for (t_start, t_end) in exclude_rejected(t_start, t_end, &rejected) {
+            time_ranges.push((t_start, t_end));
+            let expr = col("start")
+                .gt_eq(lit(t_start))
+                .and(col("start").lt(lit(t_end)));
+            let sel_col = &[col("^.*mean$")];
+            gait_ldfs
+                .push(gait_ldf.clone().filter(expr.clone()).select(sel_col));
+            ls_ldfs.push(ls_ldf.clone().filter(expr.clone()).select(sel_col));
+            rs_ldfs.push(rs_ldf.clone().filter(expr.clone()).select(sel_col));
+            db_ldfs.push(db_ldf.clone().filter(expr.clone()).select(sel_col));
+        }
     }
+    /* only rejected strides inside selection are reported */
+    let rejected = rejected
+        .into_iter()
+        .filter(|r| ranges.iter().any(|(s, e)| *s <= r.start && r.start < *e))
+        .collect::<Vec<Rejected>>();
     let gt_mean = concat(gait_ldfs, true)?.mean().collect()?;
     let ls_mean = concat(ls_ldfs, true)?.mean().collect()?;
     let rs_mean = concat(rs_ldfs, true)?.mean().collect()?;
     let db_mean = concat(db_ldfs, true)?.mean().collect()?;
 
+    /* only profile columns go into max/min, time is kept for filtering */
+    let stat_df = match &opts.profile {
+        Some(p) => p
+            .with_leading(&["time"])
+            .apply(&df)
+            .map_err(|e| e.in_file(&file))?,
+        None => df,
+    };
+
     /* iter valid step get max/min amoung all col in data */
     let lazy_dfs = vec_ranges.iter().fold(
         Vec::with_capacity(vec_ranges.len()),
         |mut v, (start, end)| {
             v.push(
-                df.clone()
+                stat_df
+                    .clone()
                     .lazy()
                     .filter(
                         col("time")
                             .gt_eq(lit(*start))
                             .and(col("time").lt(lit(*end))),
                     )
-                    .with_column(all().exclude(&["time"]).max().suffix("_max"))
-                    .with_column(all().exclude(&["time"]).min().suffix("_min")),
+                    .with_column(all().exclude(["time"]).max().suffix("_max"))
+                    .with_column(all().exclude(["time"]).min().suffix("_min")),
             );
             v
         },
     );
     let data_df = concat(lazy_dfs, true)?
         .mean()
-        .drop_columns(&["time"])
+        .drop_columns(["time"])
         .collect()?;
 
     /* basic info column */
     let info_df = df![
-        "filename" => &[filename.clone()],
+        "filename" => &[filename],
         "selection" => &[str_ranges.join(" ")],
+        REJECTED_KEY => &[format_rejected(&rejected)],
     ]?;
 
     /* concat all column */
-    let mut result_df =
-        hor_concat_df(&[info_df, gt_mean, ls_mean, rs_mean, db_mean, data_df])?;
+    let report = SpatioTemporal::new(&events, &time_ranges);
+    let mut result_df = hor_concat_df(&[
+        info_df,
+        gt_mean,
+        ls_mean,
+        rs_mean,
+        db_mean,
+        report.to_df()?,
+        data_df,
+    ])?;
 
     /* stdout result api */
     let resp = json!({
-        "ExportFile": save_csv(&mut result_df, &save_dir.display().to_string(), &format!("{}-result.csv", outfile)),
+        "SpatioTemporal": report.to_json(),
+        "Rejected": rejected.iter().map(|r| json!({
+            "start": r.start,
+            "end": r.end,
+            "reasons": r.reasons.iter().map(|r| r.to_string()).collect::<Vec<String>>(),
+            "duration_z": r.duration_z,
+        })).collect::<Vec<Value>>(),
+        "ExportFile": save_csv(&mut result_df, &save_dir.display().to_string(), &format!("{}{}", outfile, RESULT_SUFFIX))?,
     });
+
+    /* selection strategy is the one of split when header is used */
+    let mut params = opts.params();
+    params["selection"] = json!(format_selection(&ranges));
+    let strategy = match selection {
+        Selection::Index(_) => Some("index".to_string()),
+        Selection::Time(_) => Some("time".to_string()),
+        Selection::Header => recording.header.get(STRATEGY_KEY),
+    };
+    let export_file = resp["ExportFile"].as_str().unwrap_or_default();
+    upstream(Provenance::new("export", params), &recording.header, &file)?
+        .strategy(strategy)
+        .write_sidecar(&save_dir.join(export_file))?;
     Ok(resp)
 }
+
+/// provenance of a table made from recording `file`, with remap tables
+/// of the split that wrote it
+pub(crate) fn upstream(
+    provenance: Provenance,
+    header: &RecordingHeader,
+    file: &Path,
+) -> Result<Provenance> {
+    let mut provenance = provenance.input(&get_file_name(file)?, file)?;
+    if let Some(split) = Provenance::from_header(header, file) {
+        provenance.remap = split.remap;
+    }
+    Ok(provenance)
+}
+
+/// phases with duration column named `name`
+fn phase_ldf(df: &DataFrame, name: &str) -> LazyFrame {
+    df.clone()
+        .lazy()
+        .with_column((col("end") - col("start")).alias(name))
+}
+
+fn support_ldf(phases: &[Phase], name: &str) -> Result<LazyFrame> {
+    if phases.is_empty() {
+        return Err(GaitError::EmptyGait { path: None });
+    }
+    Ok(phase_ldf(&phases_df(phases)?, name))
+}
+
+/// `[start, end)` without rejected strides
+fn exclude_rejected(
+    start: f64,
+    end: f64,
+    rejected: &[Rejected],
+) -> Vec<(f64, f64)> {
+    let mut ranges = vec![];
+    let mut from = start;
+    for r in rejected.iter().filter(|r| r.end > start && r.start < end) {
+        if r.start > from {
+            ranges.push((from, r.start));
+        }
+        from = from.max(r.end);
+    }
+    if from < end {
+        ranges.push((from, end));
+    }
+    ranges
+}
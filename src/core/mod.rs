@@ -1,6 +1,7 @@
-pub mod export;
-pub mod concat;
-pub mod split;
 pub mod check;
 pub mod clean;
+pub mod concat;
 pub mod diff;
+pub mod export;
+pub mod normalize;
+pub mod split;
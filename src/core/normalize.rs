@@ -0,0 +1,153 @@
+use serde_json::{json, Value};
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use crate::core::export::{upstream, ExportOptions};
+use crate::error::{GaitError, Result};
+use crate::utils::events::ensure_contact;
+use crate::utils::gait::{GaitEvents, Side};
+use crate::utils::header::STRATEGY_KEY;
+use crate::utils::noraxon::NoraxonReader;
+use crate::utils::normalize::{cycles_df, normalize_cycles};
+use crate::utils::preprocess::remap_contact;
+use crate::utils::provenance::Provenance;
+use crate::utils::quality::{rejected_strides, REJECTED_KEY};
+use crate::utils::selection::Selection;
+use crate::utils::util::*;
+
+/// time normalised gait cycles of strides starting inside header
+/// selection, every stride is used if selection is empty, rejected
+/// strides are left out
+pub fn normalizer(
+    file: PathBuf,
+    save_dir: PathBuf,
+    side: Option<Side>,
+    opts: &ExportOptions,
+) -> Result<Value> {
+    create_dir_all(&save_dir).map_err(GaitError::io(&save_dir))?;
+    let outfile = get_file_stem(&file)?;
+
+    let recording = NoraxonReader::new(&file).finish()?;
+    let selection = recording.selection().to_vec();
+    let df = ensure_contact(recording.data, opts.source)
+        .map_err(|e| e.in_file(&file))?;
+    let events = remap_contact(df.clone(), &opts.contact)
+        .and_then(|sup_df| GaitEvents::from_contact(&sup_df))
+        .map_err(|e| e.in_file(&file))?;
+    /* strides of chosen foot, leading foot by default */
+    let strides = match side {
+        Some(side) => events.strides(side),
+        None => events.gait().map_err(|e| e.in_file(&file))?,
+    };
+    let rejected = rejected_strides(
+        &df,
+        &events,
+        &strides,
+        &opts.reject,
+        recording.header.get(REJECTED_KEY),
+    )
+    .map_err(|e| e.in_file(&file))?;
+    let strides = Selection::Header
+        .strides(&strides, &selection)
+        .map_err(|e| e.in_file(&file))?
+        .into_iter()
+        .filter(|s| rejected.iter().all(|r| !r.contains(s.start)))
+        .collect::<Vec<_>>();
+    if strides.is_empty() {
+        return Err(GaitError::EmptyGait { path: Some(file) });
+    }
+
+    let df = match &opts.profile {
+        Some(p) => p
+            .with_leading(&["time"])
+            .apply(&df)
+            .map_err(|e| e.in_file(&file))?,
+        None => df,
+    };
+    let curves = normalize_cycles(&df, &strides)?;
+    let mut result_df = cycles_df(&curves)?;
+
+    /* stdout result api */
+    let resp = json!({
+        "Strides": strides.len(),
+        "NormalizeFile": save_csv(&mut result_df, &save_dir.display().to_string(), &format!("{}-normalized.csv", outfile))?,
+    });
+
+    let mut params = opts.params();
+    params["side"] = json!(side.map(|s| s.to_string()));
+    let normalize_file = resp["NormalizeFile"].as_str().unwrap_or_default();
+    upstream(
+        Provenance::new("normalize", params),
+        &recording.header,
+        &file,
+    )?
+    .strategy(recording.header.get(STRATEGY_KEY))
+    .write_sidecar(&save_dir.join(normalize_file))?;
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::preprocess::{LT_CONTACT, RT_CONTACT};
+    use std::fs;
+
+    /// left on 2-8, 12-18, ..., right off 3-7, 13-17, ..., one sample a
+    /// second as in Noraxon exports, `a` is twice `time`
+    fn recording(selection: &str) -> String {
+        let mut text = format!(
+            "type,exported with version,selection\n\
+             gait,3.18.98,{}\n\ntime,{},{},a\n",
+            selection, LT_CONTACT, RT_CONTACT
+        );
+        for i in 0..40 {
+            let left = if (2..8).contains(&(i % 10)) { 1000 } else { 0 };
+            let right = if (3..7).contains(&(i % 10)) { 0 } else { 1000 };
+            let time = i as f64;
+            text.push_str(&format!(
+                "{:.1},{},{},{:.1}\n",
+                time,
+                left,
+                right,
+                time * 2.
+            ));
+        }
+        text
+    }
+
+    /// fresh dir for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-normalize-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn normalize(name: &str, selection: &str) -> Result<Value> {
+        let dir = test_dir(name);
+        let file = dir.join("rec.csv");
+        fs::write(&file, recording(selection)).unwrap();
+        normalizer(file, dir.join("out"), None, &ExportOptions::default())
+    }
+
+    #[test]
+    fn strides_starting_in_selection() {
+        // strides 2-12, 12-22 and 22-32, the last ends outside
+        let resp = normalize("selection", "10-25").unwrap();
+        assert_eq!(resp["Strides"], 2);
+        let resp = normalize("all", "").unwrap();
+        assert_eq!(resp["Strides"], 3);
+    }
+
+    #[test]
+    fn selection_without_stride() {
+        assert!(matches!(
+            normalize("none", "33-39"),
+            Err(GaitError::InvalidSelection { .. })
+        ));
+    }
+}
@@ -1,148 +1,486 @@
+use crate::error::{GaitError, Result};
+use crate::utils::batch::{par_map, par_map_progress};
+use crate::utils::bouts::detect_bouts;
+use crate::utils::contact::ContactPolicy;
+use crate::utils::deid::Deidentifier;
+use crate::utils::discover::Discovery;
+use crate::utils::events::{ensure_contact, ContactSource};
+use crate::utils::gait::{phases_df, GaitEvents, Phase};
+use crate::utils::header::STRATEGY_KEY;
+use crate::utils::manifest::{Manifest, Stamp};
+use crate::utils::noraxon::{NoraxonReader, NoraxonWriter, Recording};
 use crate::utils::preprocess::*;
-use crate::utils::util::*;
+use crate::utils::profile::ColumnProfile;
+use crate::utils::provenance::Provenance;
+use crate::utils::quality::*;
+use crate::utils::recording::{Posture, RecordingId};
+use crate::utils::remap::{remap_candidates, RemapChoice};
 
 use polars::prelude::*;
-use std::borrow::Cow;
+use serde_json::{json, Map, Value};
+use std::fmt;
 use std::fs::create_dir_all;
-use std::fs::rename;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-pub fn split(
-    file: &PathBuf,
-    save_dir: &PathBuf,
-    percent: usize,
-    remap_csv_dir: &PathBuf,
-    mut c: Option<Box<dyn FnMut(&String) -> ()>>,
-) -> Result<()> {
-    create_dir_all(&save_dir)?;
-    let file = file.display().to_string();
-    if let Some(ref mut c) = c {
-        c(&file);
-    }
-    /* read file */
-    let filename = Path::new(&file)
-        .file_name()
-        .expect("Err get input file stem")
-        .to_str()
-        .unwrap()
-        .to_string();
-    let name_vec = filename.split("-").collect::<Vec<&str>>();
-    if name_vec.len() < 10 {
-        return Err(PolarsError::InvalidOperation(Cow::Borrowed(
-            "Parse name failed",
-        )));
-    }
-    let tmp_filename = format!("{}.tmp", filename);
-    let saved_path = Path::new(&save_dir)
-        .join(&tmp_filename)
-        .to_str()
-        .unwrap()
-        .to_string();
-    extract_header(&file, &saved_path);
-    let mut header_df = CsvReader::from_path(&saved_path)?.finish()?;
-    let version = header_df
-        .column("exported with version")?
-        .head(Some(1))
-        .str_value(0)
-        .to_string();
-    let remap_csv = remap_csv_dir.join(format!("{}.csv", version));
-
-    let (ori_key, new_key) = match get_keys(&remap_csv.display().to_string()) {
-        Ok(keys) => keys,
-        Err(_) => {
-            std::fs::remove_file(saved_path).unwrap();
-            return Err(PolarsError::NotFound(remap_csv.display().to_string()));
-        }
-    };
-    // let (ori_key, new_key) = get_keys(&remap_csv.display().to_string())
-    //     .unwrap_or_else(|e| panic!("{:?} {}", &remap_csv, e));
-    match load_csv(&file, &ori_key, &new_key) {
-        Ok(mut df) => {
-            /* preprocess data df */
-            let mut export_df = df.clone();
-            df = remap_contact(df)?;
-            df = split_support(df)?;
-
-            /* get support df */
-            let gait_df = cal_gait(&df)?;
-            let range_value = if name_vec[6] == "2" {
-                let range_df = get_select_df(
-                    &gait_df,
-                    gait_df.height(),
-                    gait_df.height() / 2,
-                    percent,
-                );
-                get_range_string(&range_df)
-            } else if name_vec[6] == "1" {
-                let half = gait_df.height() / 2;
-                let range_df_1 =
-                    get_select_df(&gait_df, half, half / 2, percent);
-                let range_df_2 = get_select_df(
-                    &gait_df,
-                    half,
-                    half + if gait_df.height() % 2 == 0 { 1 } else { 0 }
-                        + (half / 2),
-                    percent,
-                );
-                format!(
-                    "{} {}",
-                    get_range_string(&range_df_1),
-                    get_range_string(&range_df_2)
-                )
-            } else {
-                "".to_string()
-            };
-
-            header_df = header_df
-                .lazy()
-                .with_column(lit(range_value).alias("selection"))
-                .drop_columns(["last_name", "first_name"])
-                .collect()?;
-            save_csv(
-                &mut header_df,
-                &save_dir.display().to_string(),
-                &tmp_filename,
-            );
-            append_df2header(
-                &mut export_df,
-                &save_dir.display().to_string(),
-                &tmp_filename,
-            );
-            rename(saved_path, save_dir.join(filename)).unwrap_or_else(|e| {
-                panic!("{}", e);
-            });
-            return Ok(());
+/// called with the input file path before it's processed
+pub type SplitCallback = Box<dyn FnMut(&String)>;
+
+/// settings shared by every file of a split batch
+#[derive(Debug, Clone)]
+pub struct SplitOptions {
+    /// picks selection ranges among kept strides
+    pub strategy: Arc<dyn SelectionStrategy>,
+    /// dir to resolve remap table by export version
+    pub remap_csv_dir: PathBuf,
+    /// extra remap tables merged over the version table
+    pub remap_tables: Vec<PathBuf>,
+    /// keep only profile columns in output
+    pub profile: Option<ColumnProfile>,
+    pub contact: ContactPolicy,
+    /// contact sensor or IMU detected contact
+    pub source: ContactSource,
+    /// strides dropped before selection
+    pub reject: RejectPolicy,
+    /// header fields and file name parts removed from output
+    pub deid: Deidentifier,
+}
+
+impl SplitOptions {
+    /// centred `percent` window, see `Centred`
+    pub fn new<P: AsRef<Path>>(percent: usize, remap_csv_dir: P) -> Self {
+        Self {
+            strategy: Arc::new(Centred { percent }),
+            remap_csv_dir: remap_csv_dir.as_ref().to_path_buf(),
+            remap_tables: vec![],
+            profile: None,
+            contact: ContactPolicy::default(),
+            source: ContactSource::default(),
+            reject: RejectPolicy::default(),
+            deid: Deidentifier::default(),
         }
-        Err(e) => {
-            return Err(e);
+    }
+
+    /// settings that change split output, remap tables split may pick
+    /// and extra remap tables by content
+    pub fn params(&self) -> Result<Value> {
+        let mut tables = remap_candidates(&self.remap_csv_dir)?;
+        tables.extend(self.remap_tables.iter().cloned());
+        let remap = tables
+            .iter()
+            .map(|t| Ok((t.display().to_string(), json!(Stamp::of(t)?.hash))))
+            .collect::<Result<Map<String, Value>>>()?;
+        let mut params = json!({
+            "strategy": self.strategy.describe(),
+            "remap": remap,
+        });
+        if let (Some(params), Value::Object(settings)) =
+            (params.as_object_mut(), self.settings())
+        {
+            params.extend(settings);
         }
+        Ok(params)
     }
+
+    /// settings besides strategy and remap tables, which provenance
+    /// records on their own
+    fn settings(&self) -> Value {
+        json!({
+            "profile": self.profile.as_ref().map(|p| &p.columns),
+            "contact": self.contact.to_string(),
+            "events": self.source.to_string(),
+            "reject": self.reject.to_string(),
+            "deid": self.deid.params(),
+        })
+    }
+}
+
+/// outcome of `batch_split`
+#[derive(Debug)]
+pub struct BatchSplit {
+    /// remap choice or error of every processed file, in path order
+    pub results: Vec<(PathBuf, Result<RemapChoice>)>,
+    /// files split before with same settings and unchanged since
+    pub skipped: Vec<PathBuf>,
 }
 
-fn load_csv<P, K>(filename: P, ori_key: K, new_key: K) -> Result<DataFrame>
+/// split every discovered recording into the same sub-tree of `save_dir`
+/// on `jobs` threads (0 uses every core), recordings already split with
+/// the same settings are skipped unless `force`, `on_done` is called
+/// with each split file, files done and files to split
+pub fn batch_split<P>(
+    discovery: &Discovery,
+    save_dir: &Path,
+    opts: &SplitOptions,
+    jobs: usize,
+    force: bool,
+    on_done: P,
+) -> Result<BatchSplit>
 where
-    P: AsRef<Path>,
-    K: AsRef<Vec<String>>,
+    P: Fn(&Path, usize, usize) + Sync + Send,
 {
-    let mut df = CsvReader::from_path(filename.as_ref())?
-        .with_skip_rows(3)
-        .with_dtypes(Some(&Schema::from([
-            Field::new(
-                "Noraxon MyoMotion-Joints-Shoulder RT-Abduction Hrz (deg)",
-                DataType::Float64,
-            ),
-            Field::new(
-                "Noraxon MyoMotion-Joints-Shoulder LT-Abduction Hrz (deg)",
-                DataType::Float64,
-            ),
-        ])))
+    opts.deid.ensure_outside(save_dir)?;
+    let files = discovery.find()?;
+    let mut manifest = Manifest::open(save_dir, "split", opts.params()?);
+    let checks = par_map(&files, jobs, |found| {
+        manifest.check(save_dir, &found.key(), &found.path)
+    });
+    let mut todo = vec![];
+    let mut skipped = vec![];
+    for (found, check) in files.into_iter().zip(checks) {
+        match check? {
+            (true, _) if !force => skipped.push(found.path),
+            (_, stamp) => todo.push((found, stamp)),
+        }
+    }
+
+    let results = par_map_progress(
+        &todo,
+        jobs,
+        |(found, _)| split(&found.path, &found.mirror(save_dir), opts, None),
+        |(found, _), _, done| on_done(&found.path, done, todo.len()),
+    );
+    /* output is in same sub dir as input, under pseudonym if any */
+    for ((found, stamp), result) in todo.iter().zip(&results) {
+        let key = found.key();
+        match (result, opts.deid.file_name(&found.path)) {
+            (Ok(_), Ok(name)) => {
+                let output = found.relative.join(name).display().to_string();
+                manifest.record(&key, stamp.clone(), vec![output])
+            }
+            _ => manifest.remove(&key),
+        }
+    }
+    manifest.save(save_dir)?;
+    opts.deid.save()?;
+    Ok(BatchSplit {
+        results: todo
+            .into_iter()
+            .map(|(found, _)| found.path)
+            .zip(results)
+            .collect(),
+        skipped,
+    })
+}
+
+pub fn split(
+    file: &Path,
+    save_dir: &Path,
+    opts: &SplitOptions,
+    mut c: Option<SplitCallback>,
+) -> Result<RemapChoice> {
+    create_dir_all(save_dir).map_err(GaitError::io(save_dir))?;
+    if let Some(ref mut c) = c {
+        c(&file.display().to_string());
+    }
+    /* read file */
+    let id = RecordingId::from_path(file)?;
+    let Recording {
+        mut header,
+        data: mut export_df,
+        remap,
+    } = NoraxonReader::new(file)
+        .with_remap_dir(&opts.remap_csv_dir)
+        .with_remap_tables(&opts.remap_tables)
         .finish()?;
+    // synthetic contact is written out, so export and chart_gui use it too
+    export_df =
+        ensure_contact(export_df, opts.source).map_err(|e| e.in_file(file))?;
+    let (strides, rejected) =
+        kept_strides(&export_df, opts).map_err(|e| e.in_file(file))?;
+    let ranges = opts
+        .strategy
+        .select(&export_df, &strides, &id.posture)
+        .map_err(|e| e.in_file(file))?;
+
+    if let Some(profile) = &opts.profile {
+        // export, normalize and chart_gui need time and contacts
+        export_df = profile
+            .with_leading(&["time", LT_CONTACT, RT_CONTACT])
+            .apply(&export_df)
+            .map_err(|e| e.in_file(file))?;
+    }
+    header.selection = Some(ranges);
+    header.set_extra(STRATEGY_KEY, opts.strategy.describe());
+    // stale list of an earlier split is overwritten as well
+    if !rejected.is_empty() || header.get(REJECTED_KEY).is_some() {
+        header.set_extra(REJECTED_KEY, format_rejected(&rejected));
+    }
+    // reader always resolves a table when given a remap dir
+    let remap = remap.ok_or_else(|| GaitError::RemapNotFound {
+        path: opts.remap_csv_dir.clone(),
+    })?;
+    let output = opts.deid.file_name(file)?;
+    Provenance::new("split", opts.settings())
+        .strategy(Some(opts.strategy.describe()))
+        .remap(&remap)?
+        .input(&output, file)?
+        .write_header(&mut header);
+    opts.deid.header(&mut header)?;
+    NoraxonWriter::new(save_dir.join(output))
+        .finish(&header, &mut export_df)?;
+    Ok(remap)
+}
+
+/// gait cycles kept and rejected by policy
+fn kept_strides(
+    df: &DataFrame,
+    opts: &SplitOptions,
+) -> Result<(Vec<Phase>, Vec<Rejected>)> {
     /* preprocess data df */
-    if df.width() > new_key.as_ref().len() {
-        df = df.select(ori_key.as_ref())?; // select original key
-        rename_df(&mut df, ori_key.as_ref(), new_key.as_ref())?;
+    let events =
+        GaitEvents::from_contact(&remap_contact(df.clone(), &opts.contact)?)?;
+    let metrics = stride_metrics(df, &events, &events.gait()?)?;
+    Ok(opts.reject.apply(&metrics))
+}
+
+/// picks selection ranges among kept gait cycles, custom strategies
+/// can be set on `SplitOptions`
+pub trait SelectionStrategy: fmt::Debug + Send + Sync {
+    /// `df` is the remapped recording, `strides` are in time order
+    fn select(
+        &self,
+        df: &DataFrame,
+        strides: &[Phase],
+        posture: &Posture,
+    ) -> Result<Vec<(f64, f64)>>;
+
+    /// name and parameters recorded in header, e.g. `centred:70:4`
+    fn describe(&self) -> String;
+}
+
+/// bouts with fewer strides get no selection by default
+pub const MIN_BOUT_STRIDES: usize = 4;
+
+/// centred `percent` window by posture: one over treadmill trial, one
+/// per pass of walking trial, which is assumed to be two passes, none
+/// for other postures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Centred {
+    pub percent: usize,
+}
+
+impl SelectionStrategy for Centred {
+    fn select(
+        &self,
+        _: &DataFrame,
+        strides: &[Phase],
+        posture: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        match posture {
+            Posture::Treadmill => Ok(vec![centred(strides, self.percent)?]),
+            Posture::Walking => two_passes(strides, self.percent),
+            Posture::Other(_) => Ok(vec![]),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("centred:{}", self.percent)
+    }
+}
+
+/// centred `percent` window in every straight bout between pelvis turns,
+/// needs pelvis course or yaw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bouts {
+    pub percent: usize,
+    /// bouts with fewer strides are skipped
+    pub min_strides: usize,
+}
+
+impl SelectionStrategy for Bouts {
+    fn select(
+        &self,
+        df: &DataFrame,
+        strides: &[Phase],
+        _: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        let ranges = detect_bouts(df)?
+            .iter()
+            .map(|b| b.strides(strides))
+            .filter(|s| s.len() >= self.min_strides)
+            .map(|s| centred(&s, self.percent))
+            .collect::<Result<Vec<(f64, f64)>>>()?;
+        if ranges.is_empty() {
+            return Err(GaitError::EmptyGait { path: None });
+        }
+        Ok(ranges)
+    }
+
+    fn describe(&self) -> String {
+        format!("bouts:{}:{}", self.percent, self.min_strides)
+    }
+}
+
+/// `count` strides in the middle, all if there are fewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrideCount {
+    pub count: usize,
+}
+
+impl SelectionStrategy for StrideCount {
+    fn select(
+        &self,
+        _: &DataFrame,
+        strides: &[Phase],
+        _: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        let start = strides.len().saturating_sub(self.count) / 2;
+        let end = (start + self.count).min(strides.len());
+        Ok(vec![span(&strides[start..end])?])
+    }
+
+    fn describe(&self) -> String {
+        format!("count:{}", self.count)
+    }
+}
+
+/// strides lying completely in `[start, end]` second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl SelectionStrategy for TimeWindow {
+    fn select(
+        &self,
+        _: &DataFrame,
+        strides: &[Phase],
+        _: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        let inside = strides
+            .iter()
+            .filter(|s| self.start <= s.start && s.end <= self.end)
+            .cloned()
+            .collect::<Vec<Phase>>();
+        Ok(vec![span(&inside)?])
+    }
+
+    fn describe(&self) -> String {
+        format!("time:{}:{}", self.start, self.end)
+    }
+}
+
+/// every stride but the first `first` and last `last` ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skip {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl SelectionStrategy for Skip {
+    fn select(
+        &self,
+        _: &DataFrame,
+        strides: &[Phase],
+        _: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        let end = strides.len().saturating_sub(self.last);
+        Ok(vec![span(&strides[self.first.min(end)..end])?])
+    }
+
+    fn describe(&self) -> String {
+        format!("skip:{}:{}", self.first, self.last)
+    }
+}
+
+/// first to last valid stride
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllStrides;
+
+impl SelectionStrategy for AllStrides {
+    fn select(
+        &self,
+        _: &DataFrame,
+        strides: &[Phase],
+        _: &Posture,
+    ) -> Result<Vec<(f64, f64)>> {
+        Ok(vec![span(strides)?])
+    }
+
+    fn describe(&self) -> String {
+        "all".to_string()
+    }
+}
+
+/// built-in strategy from `centred[:<percent>]`,
+/// `bouts[:<percent>[:<min bout strides>]]`, `count:<n>`,
+/// `time:<start>:<end>`, `skip:<first>:<last>` or `all`, missing
+/// percent and min bout strides are taken from arguments
+pub fn parse_strategy(
+    spec: &str,
+    percent: usize,
+    min_bout_strides: usize,
+) -> std::result::Result<Arc<dyn SelectionStrategy>, String> {
+    fn number<T: std::str::FromStr>(v: &str) -> std::result::Result<T, String> {
+        v.trim()
+            .parse::<T>()
+            .map_err(|_| format!("'{}' is not a valid number", v))
+    }
+    let parts = spec.trim().split(':').collect::<Vec<&str>>();
+    let (name, args) = (parts[0], &parts[1..]);
+    let percent = args.first().map_or(Ok(percent), |v| number(v))?;
+    let min_strides =
+        args.get(1).map_or(Ok(min_bout_strides), |v| number(v))?;
+    Ok(match (name, args) {
+        ("centred", [] | [_]) => Arc::new(Centred { percent }),
+        ("bouts", [] | [_] | [_, _]) => Arc::new(Bouts {
+            percent,
+            min_strides,
+        }),
+        ("count", [count]) => Arc::new(StrideCount {
+            count: number(count)?,
+        }),
+        ("time", [start, end]) => Arc::new(TimeWindow {
+            start: number(start)?,
+            end: number(end)?,
+        }),
+        ("skip", [first, last]) => Arc::new(Skip {
+            first: number(first)?,
+            last: number(last)?,
+        }),
+        ("all", []) => Arc::new(AllStrides),
+        _ => {
+            return Err(format!(
+                "unknown strategy '{}', expect centred, bouts, count:<n>, \
+                 time:<start>:<end>, skip:<first>:<last> or all",
+                spec
+            ))
+        }
+    })
+}
+
+/// centred `percent` window over strides
+fn centred(strides: &[Phase], percent: usize) -> Result<(f64, f64)> {
+    let gait_df = phases_df(strides)?;
+    let len = gait_df.height();
+    get_range_tuple(&get_select_df(&gait_df, len, len / 2, percent))
+}
+
+/// centred `percent` windows at 1/4 and 3/4 of strides
+fn two_passes(strides: &[Phase], percent: usize) -> Result<Vec<(f64, f64)>> {
+    let gait_df = phases_df(strides)?;
+    let half = gait_df.height() / 2;
+    let range_df_1 = get_select_df(&gait_df, half, half / 2, percent);
+    let range_df_2 = get_select_df(
+        &gait_df,
+        half,
+        half + if gait_df.height() % 2 == 0 { 1 } else { 0 } + (half / 2),
+        percent,
+    );
+    Ok(vec![
+        get_range_tuple(&range_df_1)?,
+        get_range_tuple(&range_df_2)?,
+    ])
+}
+
+/// first stride start to last stride end
+fn span(strides: &[Phase]) -> Result<(f64, f64)> {
+    match (strides.first(), strides.last()) {
+        (Some(first), Some(last)) => Ok((first.start, last.end)),
+        _ => Err(GaitError::EmptyGait { path: None }),
     }
-    Ok(df)
 }
 
 fn get_select_df(
@@ -152,26 +490,15 @@ fn get_select_df(
     percent: usize,
 ) -> DataFrame {
     let range = length * percent / 100;
-    let start = middle - range / 2;
+    let start = middle.saturating_sub(range / 2);
     df.slice(start as i64, range)
 }
 
-fn get_range_string(df: &DataFrame) -> String {
-    format!(
-        "{}-{}",
-        df.column("start")
-            .unwrap()
-            .head(Some(1))
-            .f64()
-            .unwrap()
-            .get(0)
-            .unwrap(),
-        df.column("end")
-            .unwrap()
-            .tail(Some(1))
-            .f64()
-            .unwrap()
-            .get(0)
-            .unwrap()
-    )
+fn get_range_tuple(df: &DataFrame) -> Result<(f64, f64)> {
+    let start = df.column("start")?.f64()?.into_no_null_iter().next();
+    let end = df.column("end")?.f64()?.into_no_null_iter().last();
+    match (start, end) {
+        (Some(start), Some(end)) => Ok((start, end)),
+        _ => Err(GaitError::EmptyGait { path: None }),
+    }
 }
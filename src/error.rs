@@ -0,0 +1,267 @@
+use polars::prelude::PolarsError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, GaitError>;
+
+/// error type shared by every core/utils function
+#[derive(Debug)]
+pub enum GaitError {
+    /// file name doesn't follow the recording name convention
+    FileName {
+        path: PathBuf,
+        reason: String,
+    },
+    /// required field not found in the two-line info header
+    MissingHeaderField {
+        path: PathBuf,
+        field: String,
+    },
+    /// `exported with version` has a value we can't handle
+    UnknownVersion {
+        path: PathBuf,
+        version: String,
+    },
+    /// remap table for the export version doesn't exist
+    RemapNotFound {
+        path: PathBuf,
+    },
+    /// remap table exists but can't be read as a mapping
+    InvalidRemap {
+        path: PathBuf,
+        reason: String,
+    },
+    /// no original column in data for a remapped name
+    MissingColumn {
+        path: Option<PathBuf>,
+        column: String,
+    },
+    /// column listed in a column profile not found in data
+    MissingProfileColumn {
+        path: Option<PathBuf>,
+        profile: String,
+        column: String,
+    },
+    /// foot contact column not found in data
+    MissingContact {
+        path: Option<PathBuf>,
+        column: String,
+    },
+    /// IMU channel needed for event detection not found in data
+    MissingImu {
+        path: Option<PathBuf>,
+        column: String,
+    },
+    /// no complete gait cycle could be found
+    EmptyGait {
+        path: Option<PathBuf>,
+    },
+    /// selection string can't be parsed as `start-end` pairs
+    InvalidSelection {
+        value: String,
+    },
+    /// include or exclude glob pattern can't be parsed
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+    /// de-identification policy file can't be used
+    InvalidPolicy {
+        path: Option<PathBuf>,
+        reason: String,
+    },
+    /// pseudonym map can't be read or is at an unsafe place
+    InvalidMap {
+        path: PathBuf,
+        reason: String,
+    },
+    /// file has no provenance or its sidecar can't be read
+    InvalidProvenance {
+        path: PathBuf,
+        reason: String,
+    },
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Polars(PolarsError),
+}
+
+impl GaitError {
+    pub fn io<P: AsRef<Path>>(path: P) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| GaitError::Io { path, source }
+    }
+
+    pub fn not_found<P: AsRef<Path>>(path: P) -> Self {
+        GaitError::Io {
+            path: path.as_ref().to_path_buf(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "file not found",
+            ),
+        }
+    }
+
+    /// attach file path to data errors raised without knowing the file
+    pub fn in_file<P: AsRef<Path>>(self, file: P) -> Self {
+        let file = Some(file.as_ref().to_path_buf());
+        match self {
+            GaitError::MissingContact { path: None, column } => {
+                GaitError::MissingContact { path: file, column }
+            }
+            GaitError::MissingColumn { path: None, column } => {
+                GaitError::MissingColumn { path: file, column }
+            }
+            GaitError::MissingProfileColumn {
+                path: None,
+                profile,
+                column,
+            } => GaitError::MissingProfileColumn {
+                path: file,
+                profile,
+                column,
+            },
+            GaitError::MissingImu { path: None, column } => {
+                GaitError::MissingImu { path: file, column }
+            }
+            GaitError::EmptyGait { path: None } => {
+                GaitError::EmptyGait { path: file }
+            }
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for GaitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GaitError::FileName { path, reason } => {
+                write!(
+                    f,
+                    "{}: can't parse file name, {}",
+                    path.display(),
+                    reason
+                )
+            }
+            GaitError::MissingHeaderField { path, field } => {
+                write!(
+                    f,
+                    "{}: header field '{}' not found",
+                    path.display(),
+                    field
+                )
+            }
+            GaitError::UnknownVersion { path, version } => {
+                write!(
+                    f,
+                    "{}: unknown export version '{}'",
+                    path.display(),
+                    version
+                )
+            }
+            GaitError::RemapNotFound { path } => {
+                write!(f, "remap table {} not found", path.display())
+            }
+            GaitError::InvalidRemap { path, reason } => {
+                write!(f, "remap table {}: {}", path.display(), reason)
+            }
+            GaitError::MissingColumn { path, column } => match path {
+                Some(p) => write!(
+                    f,
+                    "{}: no column remapped to '{}'",
+                    p.display(),
+                    column
+                ),
+                None => write!(f, "no column remapped to '{}'", column),
+            },
+            GaitError::MissingProfileColumn {
+                path,
+                profile,
+                column,
+            } => match path {
+                Some(p) => write!(
+                    f,
+                    "{}: column '{}' of profile '{}' not found",
+                    p.display(),
+                    column,
+                    profile
+                ),
+                None => write!(
+                    f,
+                    "column '{}' of profile '{}' not found",
+                    column, profile
+                ),
+            },
+            GaitError::MissingContact { path, column } => match path {
+                Some(p) => write!(
+                    f,
+                    "{}: contact column '{}' not found",
+                    p.display(),
+                    column
+                ),
+                None => write!(f, "contact column '{}' not found", column),
+            },
+            GaitError::MissingImu { path, column } => match path {
+                Some(p) => write!(
+                    f,
+                    "{}: IMU column '{}' not found for event detection",
+                    p.display(),
+                    column
+                ),
+                None => write!(
+                    f,
+                    "IMU column '{}' not found for event detection",
+                    column
+                ),
+            },
+            GaitError::EmptyGait { path } => match path {
+                Some(p) => write!(f, "{}: no gait cycle found", p.display()),
+                None => write!(f, "no gait cycle found"),
+            },
+            GaitError::InvalidSelection { value } => {
+                write!(f, "invalid selection '{}'", value)
+            }
+            GaitError::InvalidPattern { pattern, reason } => {
+                write!(f, "invalid pattern '{}': {}", pattern, reason)
+            }
+            GaitError::InvalidPolicy { path, reason } => match path {
+                Some(p) => {
+                    write!(f, "{}: invalid policy, {}", p.display(), reason)
+                }
+                None => write!(f, "invalid policy, {}", reason),
+            },
+            GaitError::InvalidMap { path, reason } => {
+                write!(
+                    f,
+                    "{}: invalid pseudonym map, {}",
+                    path.display(),
+                    reason
+                )
+            }
+            GaitError::InvalidProvenance { path, reason } => {
+                write!(f, "{}: invalid provenance, {}", path.display(), reason)
+            }
+            GaitError::Io { path, source } => {
+                write!(f, "{}: {}", path.display(), source)
+            }
+            GaitError::Polars(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GaitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GaitError::Io { source, .. } => Some(source),
+            GaitError::Polars(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PolarsError> for GaitError {
+    fn from(e: PolarsError) -> Self {
+        GaitError::Polars(e)
+    }
+}
@@ -0,0 +1,70 @@
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// `f` over `items` on `jobs` threads, 0 uses every core, results are in
+/// item order
+pub fn par_map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    par_map_progress(items, jobs, f, |_, _, _| {})
+}
+
+/// `par_map` calling `on_done` from worker threads after every item with
+/// the item, its result and number of items done so far
+pub fn par_map_progress<T, R, F, P>(
+    items: &[T],
+    jobs: usize,
+    f: F,
+    on_done: P,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+    P: Fn(&T, &R, usize) + Sync + Send,
+{
+    let done = AtomicUsize::new(0);
+    let run = || {
+        items
+            .par_iter()
+            .map(|item| {
+                let result = f(item);
+                on_done(item, &result, done.fetch_add(1, Ordering::SeqCst) + 1);
+                result
+            })
+            .collect()
+    };
+    match ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool.install(run),
+        Err(_) => run(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn results_in_item_order() {
+        let items = (0..100).collect::<Vec<usize>>();
+        let counts = Mutex::new(vec![]);
+        let squares = par_map_progress(
+            &items,
+            4,
+            |i| i * i,
+            |i, r, done| {
+                assert_eq!(*r, i * i);
+                counts.lock().unwrap().push(done);
+            },
+        );
+        assert_eq!(squares, items.iter().map(|i| i * i).collect::<Vec<_>>());
+        let mut counts = counts.into_inner().unwrap();
+        counts.sort();
+        assert_eq!(counts, (1..=100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::error::{GaitError, Result};
+use crate::utils::events::smooth;
+use crate::utils::gait::Phase;
+use crate::utils::util::column_f64;
+
+use polars::prelude::*;
+
+/// pelvis course, pelvis is remapped to `L`
+pub const PELVIS_COURSE: &str = "Noraxon MyoMotion-Segments-L-Course (deg)";
+/// pelvis yaw rate
+pub const PELVIS_YAW: &str = "Noraxon MyoMotion-Segments-L-Gyroscope-z (deg/s)";
+/// yaw rate a turn must exceed (deg/s)
+const TURN_RATE: f64 = 45.;
+/// course change a turn must reach (deg)
+const TURN_ANGLE: f64 = 90.;
+/// turns closer than this are one turn (s)
+const MERGE_GAP: f64 = 0.5;
+/// moving average on yaw rate (s)
+const SMOOTH_TIME: f64 = 0.5;
+
+/// straight walking between turns, end excluded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bout {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Bout {
+    /// strides lying completely in bout
+    pub fn strides(&self, strides: &[Phase]) -> Vec<Phase> {
+        strides
+            .iter()
+            .filter(|s| self.start <= s.start && s.end <= self.end)
+            .cloned()
+            .collect()
+    }
+}
+
+/// split recording into straight bouts at pelvis turns, yaw rate comes
+/// from gyroscope and turn angle from course, either one stands in for
+/// the other when missing
+pub fn detect_bouts(df: &DataFrame) -> Result<Vec<Bout>> {
+    let time = column_f64(df, "time")?.unwrap_or_default();
+    if time.len() < 2 {
+        return Ok(vec![]);
+    }
+    let dt = (time[time.len() - 1] - time[0]) / (time.len() - 1) as f64;
+    let course = column_f64(df, PELVIS_COURSE)?.map(|c| unwrap(&c));
+    let yaw = column_f64(df, PELVIS_YAW)?;
+    let (course, yaw) = match (course, yaw) {
+        (Some(c), Some(y)) => (c, y),
+        (Some(c), None) => {
+            let yaw = c.windows(2).map(|w| (w[1] - w[0]) / dt);
+            let yaw = [0.].into_iter().chain(yaw).collect();
+            (c, yaw)
+        }
+        (None, Some(y)) => {
+            let course = y.iter().scan(0., |c, v| {
+                *c += v * dt;
+                Some(*c)
+            });
+            (course.collect(), y)
+        }
+        (None, None) => {
+            return Err(GaitError::MissingImu {
+                path: None,
+                column: PELVIS_COURSE.to_string(),
+            })
+        }
+    };
+    let window = ((SMOOTH_TIME / dt) as usize).max(1);
+    let rate = smooth(&yaw.iter().map(|y| y.abs()).collect::<Vec<_>>(), window);
+
+    /* candidate turns by yaw rate, close ones merged */
+    let mut turns: Vec<(usize, usize)> = vec![];
+    let mut start = None;
+    for (i, r) in rate.iter().chain([&0.]).enumerate() {
+        match (start, *r > TURN_RATE) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                let end = i - 1;
+                match turns.last_mut() {
+                    Some(last) if time[s] - time[last.1] < MERGE_GAP => {
+                        last.1 = end
+                    }
+                    _ => turns.push((s, end)),
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    turns.retain(|(s, e)| (course[*e] - course[*s]).abs() >= TURN_ANGLE);
+
+    /* bouts are what's between turns */
+    let mut bouts = vec![];
+    let mut from = time[0];
+    for (s, e) in turns {
+        if time[s] > from {
+            bouts.push(Bout {
+                start: from,
+                end: time[s],
+            });
+        }
+        from = time[e];
+    }
+    if time[time.len() - 1] > from {
+        bouts.push(Bout {
+            start: from,
+            end: time[time.len() - 1],
+        });
+    }
+    Ok(bouts)
+}
+
+/// remove 360 degree jumps of wrapped angle
+fn unwrap(angles: &[f64]) -> Vec<f64> {
+    let mut offset = 0.;
+    let mut unwrapped = Vec::with_capacity(angles.len());
+    for (i, a) in angles.iter().enumerate() {
+        if i > 0 {
+            let diff = a - angles[i - 1];
+            if diff > 180. {
+                offset -= 360.;
+            } else if diff < -180. {
+                offset += 360.;
+            }
+        }
+        unwrapped.push(a + offset);
+    }
+    unwrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HZ: f64 = 100.;
+
+    /// 20 s walk at 100 Hz, pelvis turns `angle` degree at 90 deg/s
+    /// starting at 10 s
+    fn walk(angle: f64) -> DataFrame {
+        let time = (0..=2000).map(|i| i as f64 / HZ).collect::<Vec<f64>>();
+        let turn = angle / 90.;
+        let yaw = time
+            .iter()
+            .map(|t| {
+                if (10. ..10. + turn).contains(t) {
+                    90.
+                } else {
+                    0.
+                }
+            })
+            .collect::<Vec<f64>>();
+        let course = time
+            .iter()
+            .map(|t| (t - 10.).clamp(0., turn) * 90.)
+            .collect::<Vec<f64>>();
+        df!(
+            "time" => time,
+            PELVIS_COURSE => course,
+            PELVIS_YAW => yaw,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn straight_walk_is_one_bout() {
+        let bouts = detect_bouts(&walk(0.)).unwrap();
+        assert_eq!(
+            bouts,
+            vec![Bout {
+                start: 0.,
+                end: 20.
+            }]
+        );
+    }
+
+    #[test]
+    fn turn_splits_bouts() {
+        let bouts = detect_bouts(&walk(180.)).unwrap();
+        assert_eq!(bouts.len(), 2);
+        // turn is 10-12 s, smoothing blurs its edges
+        assert_eq!(bouts[0].start, 0.);
+        assert!((bouts[0].end - 10.).abs() < SMOOTH_TIME);
+        assert!((bouts[1].start - 12.).abs() < SMOOTH_TIME);
+        assert_eq!(bouts[1].end, 20.);
+    }
+
+    #[test]
+    fn short_turn_is_no_turn() {
+        // fast enough but below `TURN_ANGLE`
+        let bouts = detect_bouts(&walk(60.)).unwrap();
+        assert_eq!(
+            bouts,
+            vec![Bout {
+                start: 0.,
+                end: 20.
+            }]
+        );
+    }
+
+    #[test]
+    fn course_or_yaw_alone() {
+        let df = walk(180.);
+        let course = df.drop(PELVIS_YAW).unwrap();
+        assert_eq!(detect_bouts(&course).unwrap().len(), 2);
+        let yaw = df.drop(PELVIS_COURSE).unwrap();
+        assert_eq!(detect_bouts(&yaw).unwrap().len(), 2);
+        assert!(matches!(
+            detect_bouts(&yaw.drop(PELVIS_YAW).unwrap()),
+            Err(GaitError::MissingImu { .. })
+        ));
+    }
+
+    #[test]
+    fn unwrap_course() {
+        let course = unwrap(&[170., 179., -179., -170., 179.]);
+        assert_eq!(course, vec![170., 179., 181., 190., 179.]);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::error::Result;
+
+use polars::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// how one contact sample is turned into on/off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContactLevel {
+    /// on when value equals level
+    Exact(f64),
+    /// on when value >= level
+    Threshold(f64),
+    /// turn on when value >= on, turn off when value <= off,
+    /// keep last state in between
+    Hysteresis { on: f64, off: f64 },
+}
+
+/// foot contact decoding, default is `exact:1000` without debounce
+/// written as `<level>[+debounce:<samples>]`, e.g. `exact:1000`,
+/// `threshold:500`, `hysteresis:600:400+debounce:3`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactPolicy {
+    pub level: ContactLevel,
+    /// on/off runs shorter than this many samples take previous state
+    pub debounce: usize,
+}
+
+impl Default for ContactPolicy {
+    fn default() -> Self {
+        Self {
+            level: ContactLevel::Exact(1000.),
+            debounce: 0,
+        }
+    }
+}
+
+impl ContactPolicy {
+    /// decode contact column, null is treated as no contact
+    pub fn decode(&self, contact: &Series) -> Result<BooleanChunked> {
+        let values = contact.cast(&DataType::Float64)?;
+        let mut state = false;
+        let mut decoded = values
+            .f64()?
+            .into_iter()
+            .map(|v| {
+                state = match (v, self.level) {
+                    (None, _) => false,
+                    (Some(v), ContactLevel::Exact(level)) => v == level,
+                    (Some(v), ContactLevel::Threshold(level)) => v >= level,
+                    (Some(v), ContactLevel::Hysteresis { on, off }) => {
+                        if v >= on {
+                            true
+                        } else if v <= off {
+                            false
+                        } else {
+                            state
+                        }
+                    }
+                };
+                state
+            })
+            .collect::<Vec<bool>>();
+        debounce(&mut decoded, self.debounce);
+        Ok(BooleanChunked::from_slice(contact.name(), &decoded))
+    }
+}
+
+/// flip runs shorter than `min` samples to the state before them,
+/// first run is kept since nothing comes before it
+fn debounce(states: &mut [bool], min: usize) {
+    if min <= 1 {
+        return;
+    }
+    let mut start = 0;
+    while start < states.len() {
+        let end = states[start..]
+            .iter()
+            .position(|s| *s != states[start])
+            .map_or(states.len(), |p| start + p);
+        if start > 0 && end - start < min {
+            let prev = states[start - 1];
+            states[start..end].iter_mut().for_each(|s| *s = prev);
+        }
+        start = end;
+    }
+}
+
+impl FromStr for ContactPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let number = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", v))
+        };
+        let (level, debounce) = match s.split_once('+') {
+            Some((level, debounce)) => {
+                let samples = debounce
+                    .trim()
+                    .strip_prefix("debounce:")
+                    .ok_or_else(|| {
+                        format!(
+                            "expect 'debounce:<samples>', got '{}'",
+                            debounce
+                        )
+                    })?;
+                let samples =
+                    samples.trim().parse::<usize>().map_err(|_| {
+                        format!("'{}' is not a sample count", samples)
+                    })?;
+                (level, samples)
+            }
+            None => (s, 0),
+        };
+        let parts = level.trim().split(':').collect::<Vec<&str>>();
+        let level = match parts[..] {
+            ["exact", v] => ContactLevel::Exact(number(v)?),
+            ["threshold", v] => ContactLevel::Threshold(number(v)?),
+            ["hysteresis", on, off] => {
+                let (on, off) = (number(on)?, number(off)?);
+                if on < off {
+                    return Err(format!(
+                        "hysteresis on level {} is below off level {}",
+                        on, off
+                    ));
+                }
+                ContactLevel::Hysteresis { on, off }
+            }
+            _ => {
+                return Err(format!(
+                    "unknown contact policy '{}', expect exact:<v>, \
+                     threshold:<v> or hysteresis:<on>:<off>",
+                    level
+                ))
+            }
+        };
+        Ok(Self { level, debounce })
+    }
+}
+
+impl fmt::Display for ContactPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            ContactLevel::Exact(v) => write!(f, "exact:{}", v)?,
+            ContactLevel::Threshold(v) => write!(f, "threshold:{}", v)?,
+            ContactLevel::Hysteresis { on, off } => {
+                write!(f, "hysteresis:{}:{}", on, off)?
+            }
+        }
+        if self.debounce > 1 {
+            write!(f, "+debounce:{}", self.debounce)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(policy: &str, values: &[Option<f64>]) -> Vec<bool> {
+        let policy: ContactPolicy = policy.parse().unwrap();
+        let series = Series::new("contact", values);
+        policy
+            .decode(&series)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in [
+            "exact:1000",
+            "threshold:500",
+            "hysteresis:600:400",
+            "threshold:500+debounce:3",
+        ] {
+            let policy: ContactPolicy = s.parse().unwrap();
+            assert_eq!(policy.to_string(), s);
+        }
+        let policy: ContactPolicy =
+            "hysteresis:600:400+debounce:3".parse().unwrap();
+        assert_eq!(
+            policy,
+            ContactPolicy {
+                level: ContactLevel::Hysteresis {
+                    on: 600.,
+                    off: 400.
+                },
+                debounce: 3,
+            }
+        );
+        assert_eq!(
+            "exact:1000".parse::<ContactPolicy>().unwrap(),
+            ContactPolicy::default()
+        );
+    }
+
+    #[test]
+    fn reject_malformed_policies() {
+        for s in [
+            "",
+            "exact",
+            "exact:x",
+            "level:3",
+            "hysteresis:400:600",
+            "threshold:500+3",
+            "threshold:500+debounce:-1",
+        ] {
+            assert!(s.parse::<ContactPolicy>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn decode_levels() {
+        let values = [Some(0.), Some(1000.), Some(700.), None, Some(500.)];
+        assert_eq!(
+            decode("exact:1000", &values),
+            [false, true, false, false, false]
+        );
+        assert_eq!(
+            decode("threshold:500", &values),
+            [false, true, true, false, true]
+        );
+        // 500 is between levels and keeps last state, null resets it
+        let values = [Some(700.), Some(500.), Some(300.), Some(500.)];
+        assert_eq!(
+            decode("hysteresis:600:400", &values),
+            [true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn debounce_short_runs() {
+        let on = Some(1000.);
+        let off = Some(0.);
+        // short runs take the state before them, last one too
+        let values = [on, on, off, on, on, on, off, off, off, on];
+        assert_eq!(
+            decode("exact:1000+debounce:2", &values),
+            [true, true, true, true, true, true, false, false, false, false]
+        );
+        // first run is kept even if short
+        let values = [off, on, on, on];
+        assert_eq!(
+            decode("exact:1000+debounce:3", &values),
+            [false, true, true, true]
+        );
+    }
+}
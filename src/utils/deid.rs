@@ -0,0 +1,646 @@
+use crate::error::{GaitError, Result};
+use crate::utils::header::{
+    RecordingHeader, FIRST_NAME_KEY, LAST_NAME_KEY, SELECTION_KEY, VERSION_KEY,
+};
+use crate::utils::quality::REJECTED_KEY;
+use crate::utils::recording::RecordingId;
+use crate::utils::util::hex;
+
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// header keys the tools read back, never de-identified
+const PROTECTED_KEYS: [&str; 3] = [VERSION_KEY, SELECTION_KEY, REJECTED_KEY];
+
+/// recording file name component that can be pseudonymised
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameField {
+    Subject,
+    Assistant,
+    /// record date and time
+    Date,
+}
+
+impl NameField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameField::Subject => "subject",
+            NameField::Assistant => "assistant",
+            NameField::Date => "date",
+        }
+    }
+
+    fn get<'a>(&self, id: &'a mut RecordingId) -> &'a mut String {
+        match self {
+            NameField::Subject => &mut id.subject_id,
+            NameField::Assistant => &mut id.assistant_id,
+            NameField::Date => &mut id.datetime,
+        }
+    }
+}
+
+impl FromStr for NameField {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "subject" => Ok(NameField::Subject),
+            "assistant" => Ok(NameField::Assistant),
+            "date" => Ok(NameField::Date),
+            s => Err(format!(
+                "expect subject, assistant or date file name field, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NameField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// header fields to drop, hash or replace and file name components to
+/// pseudonymise, default only drops subject names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeidPolicy {
+    pub drop: Vec<String>,
+    /// replaced by salted pseudonym, reversible with pseudonym map
+    pub hash: Vec<String>,
+    /// replaced by fixed value, not reversible
+    pub replace: Vec<(String, String)>,
+    /// replaced by salted pseudonym, reversible with pseudonym map
+    pub file_name: Vec<NameField>,
+}
+
+impl Default for DeidPolicy {
+    fn default() -> Self {
+        Self {
+            drop: vec![LAST_NAME_KEY.to_string(), FIRST_NAME_KEY.to_string()],
+            hash: vec![],
+            replace: vec![],
+            file_name: vec![],
+        }
+    }
+}
+
+impl DeidPolicy {
+    /// load toml policy, every table and key is optional
+    ///
+    /// ```toml
+    /// [header]
+    /// drop = ["last_name", "first_name", "birth_date"]
+    /// hash = ["subject"]
+    /// replace = { gender = "" }
+    ///
+    /// [filename]
+    /// pseudonymise = ["subject", "assistant", "date"]
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let invalid = |reason: String| GaitError::InvalidPolicy {
+            path: Some(path.to_path_buf()),
+            reason,
+        };
+        let text = fs::read_to_string(path).map_err(GaitError::io(path))?;
+        let root: toml::value::Table =
+            toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        let mut policy = Self {
+            drop: vec![],
+            hash: vec![],
+            replace: vec![],
+            file_name: vec![],
+        };
+        for (table, value) in &root {
+            let value = value.as_table().ok_or_else(|| {
+                invalid(format!("'{}' is not a table", table))
+            })?;
+            for (key, value) in value {
+                let name = format!("{}.{}", table, key);
+                match (table.as_str(), key.as_str()) {
+                    ("header", "drop") => {
+                        policy.drop = strings(&name, value).map_err(invalid)?
+                    }
+                    ("header", "hash") => {
+                        policy.hash = strings(&name, value).map_err(invalid)?
+                    }
+                    ("header", "replace") => {
+                        policy.replace = value
+                            .as_table()
+                            .ok_or_else(|| format!("'{}' is not a table", name))
+                            .and_then(|t| {
+                                t.iter()
+                                    .map(|(k, v)| match v.as_str() {
+                                        Some(v) => Ok((k.clone(), v.into())),
+                                        None => Err(format!(
+                                            "'{}.{}' is not a string",
+                                            name, k
+                                        )),
+                                    })
+                                    .collect()
+                            })
+                            .map_err(invalid)?
+                    }
+                    ("filename", "pseudonymise") => {
+                        policy.file_name = strings(&name, value)
+                            .and_then(|fields| {
+                                fields.iter().map(|f| f.parse()).collect()
+                            })
+                            .map_err(invalid)?
+                    }
+                    _ => {
+                        return Err(invalid(format!("unknown key '{}'", name)))
+                    }
+                }
+            }
+        }
+        policy.validate().map_err(invalid)?;
+        Ok(policy)
+    }
+
+    /// every header key is handled once and none of them is needed by
+    /// the tools
+    fn validate(&self) -> std::result::Result<(), String> {
+        let mut keys = self
+            .drop
+            .iter()
+            .chain(&self.hash)
+            .chain(self.replace.iter().map(|(k, _)| k))
+            .collect::<Vec<&String>>();
+        if let Some(key) =
+            keys.iter().find(|k| PROTECTED_KEYS.contains(&k.as_str()))
+        {
+            return Err(format!("'{}' is needed by gaitool", key));
+        }
+        keys.sort();
+        if let Some(pair) = keys.windows(2).find(|p| p[0] == p[1]) {
+            return Err(format!("'{}' is listed more than once", pair[0]));
+        }
+        Ok(())
+    }
+
+    /// hashes or pseudonymises, needs a pseudonym map
+    pub fn is_reversible(&self) -> bool {
+        !self.hash.is_empty() || !self.file_name.is_empty()
+    }
+
+    /// header fields dropped or replaced by policy which `header` still
+    /// holds, hashed fields can't be told apart from their pseudonyms
+    pub fn leftovers(&self, header: &RecordingHeader) -> Vec<String> {
+        let dropped = self.drop.iter().filter(|k| header.get(k).is_some());
+        let replaced = self
+            .replace
+            .iter()
+            .filter(|(k, v)| header.get(k).is_some_and(|h| h != *v))
+            .map(|(k, _)| k);
+        dropped.chain(replaced).cloned().collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "drop": self.drop,
+            "hash": self.hash,
+            "replace": self
+                .replace
+                .iter()
+                .map(|(k, v)| (k.clone(), json!(v)))
+                .collect::<Map<String, Value>>(),
+            "filename": self
+                .file_name
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<&str>>(),
+        })
+    }
+}
+
+/// salt and pseudonyms handed out, kept apart from de-identified data
+/// since it re-identifies them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PseudonymMap {
+    salt: String,
+    /// pseudonym to original, by file name component
+    pub file_name: BTreeMap<NameField, BTreeMap<String, String>>,
+    /// pseudonym to original, by header key
+    pub header: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl PseudonymMap {
+    /// map at `path`, or a new one with random salt if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::load(path);
+        }
+        let mut salt = [0u8; 32];
+        getrandom::getrandom(&mut salt).map_err(|e| GaitError::InvalidMap {
+            path: path.to_path_buf(),
+            reason: format!("can't make salt, {}", e),
+        })?;
+        Ok(Self {
+            salt: salt.iter().map(|b| format!("{:02x}", b)).collect(),
+            file_name: BTreeMap::new(),
+            header: BTreeMap::new(),
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let invalid = |reason: String| GaitError::InvalidMap {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let text = fs::read_to_string(path).map_err(GaitError::io(path))?;
+        let v = serde_json::from_str::<Value>(&text)
+            .map_err(|e| invalid(e.to_string()))?;
+        let table = |v: &Value| {
+            v.as_object()?
+                .iter()
+                .map(|(p, o)| Some((p.clone(), o.as_str()?.to_string())))
+                .collect::<Option<BTreeMap<String, String>>>()
+        };
+        let tables = |v: &Value| {
+            v.as_object()?
+                .iter()
+                .map(|(k, t)| Some((k.clone(), table(t)?)))
+                .collect::<Option<Vec<(String, BTreeMap<String, String>)>>>()
+        };
+        let malformed = || invalid("missing or malformed field".to_string());
+        let file_name = tables(&v["filename"])
+            .ok_or_else(malformed)?
+            .into_iter()
+            .map(|(k, t)| Ok((k.parse().map_err(invalid)?, t)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            salt: v["salt"].as_str().ok_or_else(malformed)?.to_string(),
+            file_name,
+            header: tables(&v["header"])
+                .ok_or_else(malformed)?
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    /// write map readable by owner only, also when it existed before
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let map = json!({
+            "salt": self.salt,
+            "filename": self
+                .file_name
+                .iter()
+                .map(|(k, t)| (k.to_string(), json!(t)))
+                .collect::<Map<String, Value>>(),
+            "header": self.header,
+        });
+        let text = serde_json::to_string_pretty(&map)
+            .expect("json value is always serializable");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path).map_err(GaitError::io(path))?;
+        // mode only applies when the file is created
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::Permissions::from_mode(0o600);
+            file.set_permissions(mode).map_err(GaitError::io(path))?;
+        }
+        std::io::Write::write_all(&mut file, text.as_bytes())
+            .map_err(GaitError::io(path))
+    }
+
+    /// salted hash of `value`, same value of same field always gets same
+    /// pseudonym
+    fn digest(&self, field: &str, value: &str) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for part in [self.salt.as_str(), field, value] {
+            hasher.update(part.as_bytes());
+            hasher.update([0x1f]);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    /// pseudonym still valid in a recording name, date stays digits,
+    /// error if it's taken by another value
+    fn name_pseudonym(
+        &mut self,
+        field: NameField,
+        value: &str,
+    ) -> std::result::Result<String, String> {
+        let digest = self.digest(field.as_str(), value);
+        let pseudonym = match field {
+            NameField::Date => {
+                let n = digest[..8]
+                    .iter()
+                    .fold(0u64, |n, b| n << 8 | u64::from(*b));
+                format!("{:012}", n % 1_000_000_000_000)
+            }
+            _ => hex(&digest[..5]),
+        };
+        let table = self.file_name.entry(field).or_default();
+        remember(table, field.as_str(), pseudonym, value)
+    }
+
+    fn header_pseudonym(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> std::result::Result<String, String> {
+        let pseudonym = hex(&self.digest(key, value)[..8]);
+        let table = self.header.entry(key.to_string()).or_default();
+        remember(table, key, pseudonym, value)
+    }
+
+    /// original name of pseudonymised recording, unknown parts are kept
+    pub fn original_id(&self, id: &RecordingId) -> RecordingId {
+        let mut id = id.clone();
+        for (field, table) in &self.file_name {
+            let value = field.get(&mut id);
+            if let Some(original) = table.get(value.as_str()) {
+                *value = original.clone();
+            }
+        }
+        id
+    }
+
+    /// put original values back into hashed header fields, return keys
+    /// restored
+    pub fn restore_header(&self, header: &mut RecordingHeader) -> Vec<String> {
+        let mut restored = vec![];
+        for (key, table) in &self.header {
+            let original = header.get(key).and_then(|v| table.get(&v));
+            if let Some(original) = original {
+                header.set(key, original.clone());
+                restored.push(key.clone());
+            }
+        }
+        restored
+    }
+}
+
+/// policy with its pseudonym map, shared by threads of a batch
+#[derive(Debug, Clone, Default)]
+pub struct Deidentifier {
+    policy: DeidPolicy,
+    map: Option<(PathBuf, Arc<Mutex<PseudonymMap>>)>,
+}
+
+impl Deidentifier {
+    /// `map` is opened or created, it's needed when policy hashes or
+    /// pseudonymises
+    pub fn new(policy: DeidPolicy, map: Option<&Path>) -> Result<Self> {
+        let map = match map {
+            Some(path) => Some((
+                path.to_path_buf(),
+                Arc::new(Mutex::new(PseudonymMap::open(path)?)),
+            )),
+            None if policy.is_reversible() => {
+                return Err(GaitError::InvalidPolicy {
+                    path: None,
+                    reason: "hash and pseudonymise need a pseudonym map"
+                        .to_string(),
+                })
+            }
+            None => None,
+        };
+        Ok(Self { policy, map })
+    }
+
+    pub fn policy(&self) -> &DeidPolicy {
+        &self.policy
+    }
+
+    /// settings that change output
+    pub fn params(&self) -> Value {
+        let mut params = self.policy.to_json();
+        params["map"] = json!(self.map.as_ref().map(|(p, _)| p));
+        params
+    }
+
+    /// file name of `file` with policy name fields pseudonymised
+    pub fn file_name(&self, file: &Path) -> Result<String> {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let map = match &self.map {
+            Some((_, map)) if !self.policy.file_name.is_empty() => map,
+            _ => return Ok(name),
+        };
+        let mut id = RecordingId::from_path(file)?;
+        let mut map = map.lock().unwrap_or_else(|e| e.into_inner());
+        for &field in &self.policy.file_name {
+            let value = field.get(&mut id);
+            *value = map
+                .name_pseudonym(field, value)
+                .map_err(|reason| self.invalid_map(reason))?;
+        }
+        Ok(match file.extension() {
+            Some(ext) => format!("{}.{}", id, ext.to_string_lossy()),
+            None => id.to_string(),
+        })
+    }
+
+    /// drop, hash and replace header fields by policy
+    pub fn header(&self, header: &mut RecordingHeader) -> Result<()> {
+        for key in &self.policy.drop {
+            header.remove(key);
+        }
+        if let Some((_, map)) = &self.map {
+            let mut map = map.lock().unwrap_or_else(|e| e.into_inner());
+            for key in &self.policy.hash {
+                match header.get(key) {
+                    Some(value) if !value.is_empty() => {
+                        let pseudonym = map
+                            .header_pseudonym(key, &value)
+                            .map_err(|reason| self.invalid_map(reason))?;
+                        header.set(key, pseudonym)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (key, value) in &self.policy.replace {
+            if header.get(key).is_some() {
+                header.set(key, value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// map must not end up among de-identified files in `dir`
+    pub fn ensure_outside(&self, dir: &Path) -> Result<()> {
+        let path = match &self.map {
+            Some((path, _)) => path,
+            None => return Ok(()),
+        };
+        let canonical = |p: &Path| p.canonicalize().unwrap_or(p.to_path_buf());
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        if canonical(parent).starts_with(canonical(dir)) {
+            return Err(GaitError::InvalidMap {
+                path: path.clone(),
+                reason: format!(
+                    "inside output dir {}, keep it apart from data",
+                    dir.display()
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn invalid_map(&self, reason: String) -> GaitError {
+        GaitError::InvalidMap {
+            path: self
+                .map
+                .as_ref()
+                .map(|(p, _)| p.clone())
+                .unwrap_or_default(),
+            reason,
+        }
+    }
+
+    /// write pseudonyms handed out so far
+    pub fn save(&self) -> Result<()> {
+        match &self.map {
+            Some((path, map)) => {
+                map.lock().unwrap_or_else(|e| e.into_inner()).save(path)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// string list at `name` of policy
+fn strings(
+    name: &str,
+    value: &toml::Value,
+) -> std::result::Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|a| {
+            a.iter().map(|v| v.as_str().map(str::to_string)).collect()
+        })
+        .ok_or_else(|| format!("'{}' is not a list of strings", name))
+}
+
+/// record `pseudonym` of `value` in `table`, a pseudonym already
+/// standing for another value is never overwritten
+fn remember(
+    table: &mut BTreeMap<String, String>,
+    field: &str,
+    pseudonym: String,
+    value: &str,
+) -> std::result::Result<String, String> {
+    match table.get(&pseudonym) {
+        Some(original) if original != value => Err(format!(
+            "pseudonym '{}' of {} already stands for another value",
+            pseudonym, field
+        )),
+        _ => {
+            table.insert(pseudonym.clone(), value.to_string());
+            Ok(pseudonym)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "2022-11-02-17-00_101-8-1-2-[1,2]-1.csv";
+    const HEADER: &str = "exported with version,last_name,first_name,subject\n\
+                          3.18.98,Chen,Wesley,101\n";
+
+    fn map_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-deid-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("map.json")
+    }
+
+    fn policy() -> DeidPolicy {
+        DeidPolicy {
+            hash: vec!["subject".to_string()],
+            file_name: vec![
+                NameField::Subject,
+                NameField::Assistant,
+                NameField::Date,
+            ],
+            ..DeidPolicy::default()
+        }
+    }
+
+    #[test]
+    fn deidentify_and_restore() {
+        let path = map_path("round-trip");
+        let deid = Deidentifier::new(policy(), Some(&path)).unwrap();
+        let name = deid.file_name(Path::new(FILE)).unwrap();
+        let mut header =
+            RecordingHeader::from_reader(HEADER.as_bytes(), Path::new(FILE))
+                .unwrap();
+        deid.header(&mut header).unwrap();
+        deid.save().unwrap();
+
+        let id = RecordingId::from_path(&name).unwrap();
+        let original = RecordingId::from_path(FILE).unwrap();
+        assert_ne!(id.subject_id, original.subject_id);
+        assert_ne!(id.datetime, original.datetime);
+        assert_eq!(header.last_name, None);
+        assert_ne!(header.get("subject").as_deref(), Some("101"));
+
+        // same value always gets same pseudonym
+        assert_eq!(deid.file_name(Path::new(FILE)).unwrap(), name);
+
+        let map = PseudonymMap::load(&path).unwrap();
+        assert_eq!(map.original_id(&id), original);
+        assert_eq!(map.restore_header(&mut header), vec!["subject"]);
+        assert_eq!(header.get("subject").as_deref(), Some("101"));
+    }
+
+    #[test]
+    fn taken_pseudonym_is_error() {
+        let mut map = PseudonymMap::open(map_path("collision")).unwrap();
+        let pseudonym = map.name_pseudonym(NameField::Subject, "101").unwrap();
+        map.file_name
+            .get_mut(&NameField::Subject)
+            .unwrap()
+            .insert(pseudonym.clone(), "102".to_string());
+        assert!(map.name_pseudonym(NameField::Subject, "101").is_err());
+        assert_eq!(
+            map.file_name[&NameField::Subject][&pseudonym],
+            "102".to_string()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_map_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = map_path("mode");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        PseudonymMap::open(map_path("mode-new"))
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
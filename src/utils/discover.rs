@@ -0,0 +1,289 @@
+use crate::error::{GaitError, Result};
+use crate::utils::recording::{Posture, RecordingId};
+
+use glob::{MatchOptions, Pattern};
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+/// dir names the tools write into inside an input dir, never walked
+pub const OUTPUT_DIRS: [&str; 1] = ["output"];
+
+/// include pattern when none is given, every tool reads csv only
+pub const DEFAULT_INCLUDE: &str = "*.csv";
+
+/// input file found by `Discovery`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Found {
+    pub path: PathBuf,
+    /// parent dir relative to discovery root, empty at top level
+    pub relative: PathBuf,
+}
+
+impl Found {
+    /// path relative to discovery root
+    pub fn key(&self) -> String {
+        let name = self.path.file_name().unwrap_or_default();
+        self.relative.join(name).display().to_string()
+    }
+
+    /// same sub-tree under `save_dir`
+    pub fn mirror(&self, save_dir: &Path) -> PathBuf {
+        save_dir.join(&self.relative)
+    }
+}
+
+/// walk an input dir recursively and filter files by glob patterns on
+/// their path relative to root and by recording name fields, hidden
+/// files and dirs are skipped
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    root: PathBuf,
+    recursive: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    subjects: Vec<String>,
+    postures: Vec<Posture>,
+    orders: Vec<String>,
+    skip: Vec<PathBuf>,
+}
+
+impl Discovery {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            recursive: true,
+            include: vec![],
+            exclude: vec![],
+            subjects: vec![],
+            postures: vec![],
+            orders: vec![],
+            skip: vec![],
+        }
+    }
+
+    /// only read top level of root
+    pub fn flat(mut self) -> Self {
+        self.recursive = false;
+        self
+    }
+
+    /// keep only files matching one of include patterns, e.g.
+    /// `visit-1/**`, `DEFAULT_INCLUDE` until one is given
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    /// drop files matching exclude pattern, e.g. `visit-2/**`
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(parse_pattern(pattern)?);
+        Ok(self)
+    }
+
+    /// keep only recordings of subject, files not following the
+    /// recording name convention are dropped once any field is filtered
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.subjects.push(subject.to_string());
+        self
+    }
+
+    pub fn posture(mut self, posture: Posture) -> Self {
+        self.postures.push(posture);
+        self
+    }
+
+    pub fn order(mut self, order: &str) -> Self {
+        self.orders.push(order.to_string());
+        self
+    }
+
+    /// never walk into `dir`, e.g. save dir inside input dir
+    pub fn skip<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.skip.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// matched files sorted by path
+    pub fn find(&self) -> Result<Vec<Found>> {
+        let skip = self
+            .skip
+            .iter()
+            .filter_map(|d| d.canonicalize().ok())
+            .collect::<Vec<PathBuf>>();
+        let skipped = |dir: &Path| {
+            let name = dir.file_name().unwrap_or_default();
+            OUTPUT_DIRS.iter().any(|o| name == *o)
+                || dir.canonicalize().is_ok_and(|d| skip.contains(&d))
+        };
+        let mut found = vec![];
+        let mut dirs = vec![PathBuf::new()];
+        while let Some(relative) = dirs.pop() {
+            let dir = self.root.join(&relative);
+            for entry in read_dir(&dir).map_err(GaitError::io(&dir))? {
+                let path = entry.map_err(GaitError::io(&dir))?.path();
+                let name = path.file_name().unwrap_or_default();
+                // manifests and temporary files of the tools
+                if name.to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                if path.is_dir() {
+                    if self.recursive && !skipped(&path) {
+                        dirs.push(relative.join(name));
+                    }
+                } else if self.matches(&relative.join(name)) {
+                    found.push(Found {
+                        path,
+                        relative: relative.clone(),
+                    });
+                }
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        let options = MatchOptions::new();
+        let included = if self.include.is_empty() {
+            Pattern::new(DEFAULT_INCLUDE)
+                .is_ok_and(|p| p.matches_path_with(relative, options))
+        } else {
+            self.include
+                .iter()
+                .any(|p| p.matches_path_with(relative, options))
+        };
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|p| p.matches_path_with(relative, options));
+        if !included || excluded {
+            return false;
+        }
+        if self.subjects.is_empty()
+            && self.postures.is_empty()
+            && self.orders.is_empty()
+        {
+            return true;
+        }
+        match RecordingId::from_path(relative) {
+            Ok(id) => {
+                (self.subjects.is_empty()
+                    || self.subjects.contains(&id.subject_id))
+                    && (self.postures.is_empty()
+                        || self.postures.contains(&id.posture))
+                    && (self.orders.is_empty()
+                        || self.orders.contains(&id.order))
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern> {
+    Pattern::new(pattern).map_err(|e| GaitError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason: e.msg.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const TREADMILL: &str = "2022-11-02-17-00_101-8-1-2-[1]-1.csv";
+    const WALKING: &str = "2022-11-02-17-30_102-8-1-1-[1]-2.csv";
+
+    /// recordings at top level and in `visit-1`, other files around them
+    fn tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "gaitool-discover-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["visit-1", "visit-2", "output", "save"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            TREADMILL,
+            "notes.txt",
+            ".manifest.json",
+            &format!("visit-1/{}", WALKING),
+            "visit-2/other.csv",
+            "output/result.csv",
+            "save/split.csv",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        root
+    }
+
+    fn keys(discovery: &Discovery) -> Vec<String> {
+        let found = discovery.find().unwrap();
+        found.iter().map(Found::key).collect()
+    }
+
+    #[test]
+    fn csv_in_every_sub_dir() {
+        let root = tree("default");
+        let walking = format!("visit-1/{}", WALKING);
+        assert_eq!(
+            keys(&Discovery::new(&root)),
+            [TREADMILL, "save/split.csv", &walking, "visit-2/other.csv"]
+        );
+        assert_eq!(
+            keys(&Discovery::new(&root).skip(root.join("save"))),
+            [TREADMILL, &walking, "visit-2/other.csv"]
+        );
+        assert_eq!(keys(&Discovery::new(&root).flat()), [TREADMILL]);
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let root = tree("glob");
+        let discovery = Discovery::new(&root).include("visit-*/**").unwrap();
+        assert_eq!(
+            keys(&discovery),
+            [format!("visit-1/{}", WALKING), "visit-2/other.csv".into()]
+        );
+        let discovery = discovery.exclude("visit-2/**").unwrap();
+        assert_eq!(keys(&discovery), [format!("visit-1/{}", WALKING)]);
+        let notes = Discovery::new(&root).include("*.txt").unwrap();
+        assert_eq!(keys(&notes), ["notes.txt"]);
+        assert!(matches!(
+            Discovery::new(&root).include("[").unwrap_err(),
+            GaitError::InvalidPattern { .. }
+        ));
+    }
+
+    #[test]
+    fn recording_name_filters() {
+        let root = tree("fields");
+        let walking = format!("visit-1/{}", WALKING);
+        // files without recording name are dropped
+        assert_eq!(keys(&Discovery::new(&root).subject("102")), [&*walking]);
+        assert_eq!(
+            keys(&Discovery::new(&root).posture(Posture::Treadmill)),
+            [TREADMILL]
+        );
+        assert_eq!(keys(&Discovery::new(&root).order("2")), [&*walking]);
+        let both = Discovery::new(&root).subject("101").subject("102");
+        assert_eq!(keys(&both), [TREADMILL, &walking]);
+        assert!(
+            keys(&Discovery::new(&root).subject("101").order("2")).is_empty()
+        );
+    }
+
+    #[test]
+    fn mirror_relative_dir() {
+        let root = tree("mirror");
+        let found = Discovery::new(&root).order("2").find().unwrap();
+        assert_eq!(found[0].relative, Path::new("visit-1"));
+        assert_eq!(
+            found[0].mirror(Path::new("save")),
+            Path::new("save/visit-1")
+        );
+        assert!(Discovery::new(root.join("missing")).find().is_err());
+    }
+}
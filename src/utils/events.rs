@@ -0,0 +1,338 @@
+use crate::error::{GaitError, Result};
+use crate::utils::preprocess::{LT_CONTACT, RT_CONTACT};
+use crate::utils::util::column_f64;
+
+use polars::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// sagittal shank gyroscope, shank is remapped to `PSIS`
+pub const SHANK_GYRO: [&str; 2] = [
+    "Noraxon MyoMotion-Segments-PSIS LT-Gyroscope-z (deg/s)",
+    "Noraxon MyoMotion-Segments-PSIS RT-Gyroscope-z (deg/s)",
+];
+/// used when shank gyroscope is missing
+const FOOT_GYRO: [&str; 2] = [
+    "Noraxon MyoMotion-Segments-Foot LT-Gyroscope-z (deg/s)",
+    "Noraxon MyoMotion-Segments-Foot RT-Gyroscope-z (deg/s)",
+];
+/// pelvis accelerometer, pelvis is remapped to `L`
+const PELVIS_ACCEL: [&str; 3] = [
+    "L Accel Sensor X (mG)",
+    "L Accel Sensor Y (mG)",
+    "L Accel Sensor Z (mG)",
+];
+/// angular velocity a swing peak must exceed (deg/s)
+const SWING_THRESHOLD: f64 = 100.;
+/// samples of moving average on gyroscope
+const SMOOTH_WINDOW: usize = 5;
+/// initial contact is moved to pelvis impact peak within this time (s)
+const IMPACT_WINDOW: f64 = 0.04;
+
+/// where foot contact columns come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContactSource {
+    /// contact sensor columns, error if missing
+    #[default]
+    Sensor,
+    /// always detect from IMU
+    Imu,
+    /// detect from IMU when contact columns are missing or never change
+    Auto,
+}
+
+impl FromStr for ContactSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sensor" => Ok(ContactSource::Sensor),
+            "imu" => Ok(ContactSource::Imu),
+            "auto" => Ok(ContactSource::Auto),
+            s => Err(format!("expect sensor, imu or auto, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ContactSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContactSource::Sensor => write!(f, "sensor"),
+            ContactSource::Imu => write!(f, "imu"),
+            ContactSource::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// replace contact columns with IMU detected ones if source asks to
+pub fn ensure_contact(
+    mut df: DataFrame,
+    source: ContactSource,
+) -> Result<DataFrame> {
+    let use_imu = match source {
+        ContactSource::Sensor => false,
+        ContactSource::Imu => true,
+        ContactSource::Auto => [LT_CONTACT, RT_CONTACT].iter().any(|c| {
+            df.column(c).map_or(true, |s| s.n_unique().ok() == Some(1))
+        }),
+    };
+    if !use_imu {
+        return Ok(df);
+    }
+    for (i, contact) in [LT_CONTACT, RT_CONTACT].into_iter().enumerate() {
+        let states = detect_contact(&df, i)?;
+        let values = states
+            .iter()
+            .map(|c| if *c { 1000 } else { 0 })
+            .collect::<Vec<i64>>();
+        df.with_column(Series::new(contact, values))?;
+    }
+    Ok(df)
+}
+
+/// contact of one foot (0: LT, 1: RT), stance is everything outside swing
+/// swing is between the zero crossings around a sagittal gyroscope peak
+fn detect_contact(df: &DataFrame, foot: usize) -> Result<Vec<bool>> {
+    let time = imu_column(df, "time")?;
+    let mut gyro = match imu_column(df, SHANK_GYRO[foot]) {
+        Ok(gyro) => gyro,
+        Err(_) => imu_column(df, FOOT_GYRO[foot]).map_err(|_| {
+            GaitError::MissingImu {
+                path: None,
+                column: SHANK_GYRO[foot].to_string(),
+            }
+        })?,
+    };
+    // swing peak is the largest rotation, flip if sensor is mounted reversed
+    let max = gyro.iter().cloned().fold(f64::MIN, f64::max);
+    let min = gyro.iter().cloned().fold(f64::MAX, f64::min);
+    if -min > max {
+        gyro.iter_mut().for_each(|g| *g = -*g);
+    }
+    let impact = pelvis_impact(df).ok();
+
+    // peaks on smoothed signal, zero crossings on raw one since smoothing
+    // drags them towards the steep swing side
+    let mut contact = vec![true; gyro.len()];
+    let mut swings = 0;
+    for peak in swing_peaks(&smooth(&gyro, SMOOTH_WINDOW)) {
+        let toe_off = match (0..peak).rev().find(|i| gyro[*i] <= 0.) {
+            Some(i) => i + 1,
+            None => continue,
+        };
+        let mut initial = match (peak..gyro.len()).find(|i| gyro[*i] <= 0.) {
+            Some(i) => i,
+            None => continue,
+        };
+        if let Some(impact) = &impact {
+            initial = refine(initial, &time, impact);
+        }
+        contact[toe_off..initial.max(toe_off)]
+            .iter_mut()
+            .for_each(|c| *c = false);
+        swings += 1;
+    }
+    if swings == 0 {
+        return Err(GaitError::EmptyGait { path: None });
+    }
+    Ok(contact)
+}
+
+/// index of max of every run above swing threshold
+fn swing_peaks(gyro: &[f64]) -> Vec<usize> {
+    let mut peaks = vec![];
+    let mut run: Option<usize> = None;
+    for (i, g) in gyro.iter().enumerate() {
+        match (run, *g > SWING_THRESHOLD) {
+            (None, true) => run = Some(i),
+            (Some(p), true) if *g > gyro[p] => run = Some(i),
+            (Some(p), false) => {
+                peaks.push(p);
+                run = None;
+            }
+            _ => {}
+        }
+    }
+    // swing not ended before recording stops is dropped
+    peaks
+}
+
+/// pelvis acceleration magnitude
+fn pelvis_impact(df: &DataFrame) -> Result<Vec<f64>> {
+    let axes = PELVIS_ACCEL
+        .iter()
+        .map(|c| imu_column(df, c))
+        .collect::<Result<Vec<Vec<f64>>>>()?;
+    Ok((0..axes[0].len())
+        .map(|i| axes.iter().map(|a| a[i] * a[i]).sum::<f64>().sqrt())
+        .collect())
+}
+
+/// move initial contact to impact peak near it, a max on window edge
+/// is no peak and initial contact is kept
+fn refine(initial: usize, time: &[f64], impact: &[f64]) -> usize {
+    let near = |i: &usize| (time[*i] - time[initial]).abs() <= IMPACT_WINDOW;
+    let start = (0..initial)
+        .rev()
+        .take_while(near)
+        .last()
+        .unwrap_or(initial);
+    let end = (initial..impact.len())
+        .take_while(near)
+        .last()
+        .unwrap_or(initial);
+    match (start..=end).max_by(|a, b| impact[*a].total_cmp(&impact[*b])) {
+        Some(peak) if peak != start && peak != end => peak,
+        _ => initial,
+    }
+}
+
+pub(crate) fn smooth(values: &[f64], window: usize) -> Vec<f64> {
+    let half = window / 2;
+    (0..values.len())
+        .map(|i| {
+            let s = i.saturating_sub(half);
+            let e = (i + half + 1).min(values.len());
+            values[s..e].iter().sum::<f64>() / (e - s) as f64
+        })
+        .collect()
+}
+
+/// imu column `name`, missing column is an error
+fn imu_column(df: &DataFrame, name: &str) -> Result<Vec<f64>> {
+    column_f64(df, name)?.ok_or_else(|| GaitError::MissingImu {
+        path: None,
+        column: name.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 300;
+    /// swing of left foot in samples, right one is 50 samples later
+    const SWINGS: [(usize, usize); 3] = [(50, 90), (150, 190), (250, 290)];
+
+    /// 100 Hz, gyroscope is a half sine above zero in swing, slightly
+    /// negative in stance
+    fn gyro(shift: usize) -> Vec<f64> {
+        let mut gyro = vec![-20.; N];
+        for (start, end) in SWINGS {
+            let (start, end) = (start + shift, (end + shift).min(N));
+            let len = (end - start + 1) as f64;
+            for (i, g) in gyro.iter_mut().enumerate().take(end).skip(start) {
+                let phase = (i - start + 1) as f64 / len;
+                *g = 300. * (std::f64::consts::PI * phase).sin();
+            }
+        }
+        gyro
+    }
+
+    fn imu_df(columns: [&str; 2]) -> DataFrame {
+        let time = (0..N).map(|i| i as f64 / 100.).collect::<Vec<f64>>();
+        df!(
+            "time" => time,
+            columns[0] => gyro(0),
+            columns[1] => gyro(50),
+        )
+        .unwrap()
+    }
+
+    /// (toe-off, initial contact) sample indices of contact column
+    fn transitions(df: &DataFrame, column: &str) -> Vec<(usize, usize)> {
+        let contact = column_f64(df, column).unwrap().unwrap();
+        let mut swings = vec![];
+        let mut toe_off = None;
+        for i in 1..contact.len() {
+            match (contact[i - 1] > 0., contact[i] > 0.) {
+                (true, false) => toe_off = Some(i),
+                (false, true) => swings.push((toe_off.unwrap(), i)),
+                _ => {}
+            }
+        }
+        swings
+    }
+
+    #[test]
+    fn swing_between_zero_crossings() {
+        let df = ensure_contact(imu_df(SHANK_GYRO), ContactSource::Imu);
+        let df = df.unwrap();
+        assert_eq!(transitions(&df, LT_CONTACT), SWINGS);
+        assert_eq!(transitions(&df, RT_CONTACT), [(100, 140), (200, 240)]);
+    }
+
+    #[test]
+    fn reversed_sensor_is_flipped() {
+        let mut df = imu_df(SHANK_GYRO);
+        let flipped = gyro(0).iter().map(|g| -g).collect::<Vec<f64>>();
+        df.with_column(Series::new(SHANK_GYRO[0], flipped)).unwrap();
+        let df = ensure_contact(df, ContactSource::Imu).unwrap();
+        assert_eq!(transitions(&df, LT_CONTACT), SWINGS);
+    }
+
+    #[test]
+    fn foot_gyroscope_fallback() {
+        let df = ensure_contact(imu_df(FOOT_GYRO), ContactSource::Imu);
+        assert_eq!(transitions(&df.unwrap(), LT_CONTACT), SWINGS);
+
+        let df = imu_df(["a", "b"]);
+        let err = ensure_contact(df, ContactSource::Imu).unwrap_err();
+        assert!(matches!(
+            err,
+            GaitError::MissingImu { column, .. } if column == SHANK_GYRO[0]
+        ));
+    }
+
+    #[test]
+    fn initial_contact_moves_to_impact_peak() {
+        let mut df = imu_df(SHANK_GYRO);
+        let mut x = vec![1000.; N];
+        x[92] = 3000.;
+        df.with_column(Series::new(PELVIS_ACCEL[0], x)).unwrap();
+        for axis in &PELVIS_ACCEL[1..] {
+            df.with_column(Series::new(axis, vec![0.; N])).unwrap();
+        }
+        let df = ensure_contact(df, ContactSource::Imu).unwrap();
+        assert_eq!(transitions(&df, LT_CONTACT)[0], (50, 92));
+        // impact too far from the other swings leaves them as is
+        assert_eq!(transitions(&df, LT_CONTACT)[1], (150, 190));
+    }
+
+    #[test]
+    fn auto_prefers_sensor() {
+        let sensor = (0..N)
+            .map(|i| if i % 100 < 60 { 1000 } else { 0 })
+            .collect::<Vec<i64>>();
+        let mut df = imu_df(SHANK_GYRO);
+        df.with_column(Series::new(LT_CONTACT, sensor.clone()))
+            .unwrap();
+        df.with_column(Series::new(RT_CONTACT, sensor)).unwrap();
+        let kept = ensure_contact(df.clone(), ContactSource::Auto).unwrap();
+        assert_eq!(kept, df);
+
+        // a contact column that never changes is no sensor data
+        df.with_column(Series::new(RT_CONTACT, vec![0i64; N]))
+            .unwrap();
+        let df = ensure_contact(df, ContactSource::Auto).unwrap();
+        assert_eq!(transitions(&df, LT_CONTACT), SWINGS);
+
+        let df = ensure_contact(imu_df(SHANK_GYRO), ContactSource::Auto);
+        assert_eq!(transitions(&df.unwrap(), LT_CONTACT), SWINGS);
+    }
+
+    #[test]
+    fn no_swing_is_empty_gait() {
+        let time = (0..N).map(|i| i as f64 / 100.).collect::<Vec<f64>>();
+        let df = df!(
+            "time" => time,
+            SHANK_GYRO[0] => vec![-20.; N],
+            SHANK_GYRO[1] => vec![-20.; N],
+        )
+        .unwrap();
+        assert!(matches!(
+            ensure_contact(df, ContactSource::Imu),
+            Err(GaitError::EmptyGait { .. })
+        ));
+    }
+}
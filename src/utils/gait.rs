@@ -0,0 +1,374 @@
+use crate::error::{GaitError, Result};
+use crate::utils::preprocess::{LT_CONTACT, RT_CONTACT};
+
+use polars::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// foot an event or phase belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub const BOTH: [Side; 2] = [Side::Left, Side::Right];
+
+    pub fn other(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+
+    /// contact column of this foot
+    pub fn contact(self) -> &'static str {
+        match self {
+            Side::Left => LT_CONTACT,
+            Side::Right => RT_CONTACT,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "LT"),
+            Side::Right => write!(f, "RT"),
+        }
+    }
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "LT" | "lt" => Ok(Side::Left),
+            "RT" | "rt" => Ok(Side::Right),
+            s => Err(format!("expect lt or rt, got '{}'", s)),
+        }
+    }
+}
+
+/// time interval of one foot, end excluded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phase {
+    pub side: Side,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Phase {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// contact transitions of one foot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FootEvents {
+    /// off -> on, heel strike
+    pub initial_contact: Vec<f64>,
+    /// on -> off
+    pub toe_off: Vec<f64>,
+}
+
+impl FootEvents {
+    /// contact state from `t` on, a foot without events before `t`
+    /// is on if it leaves the ground first or never moves
+    fn on_at(&self, t: f64) -> bool {
+        let last =
+            |events: &[f64]| events.iter().rev().find(|e| **e <= t).cloned();
+        match (last(&self.initial_contact), last(&self.toe_off)) {
+            (Some(ic), Some(to)) => ic > to,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => {
+                match (self.initial_contact.first(), self.toe_off.first()) {
+                    (Some(ic), Some(to)) => to < ic,
+                    (Some(_), None) => false,
+                    _ => true,
+                }
+            }
+        }
+    }
+}
+
+/// heel strikes and toe-offs of both feet, every phase is derived
+/// from them and labelled by side
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GaitEvents {
+    pub left: FootEvents,
+    pub right: FootEvents,
+}
+
+impl GaitEvents {
+    /// events from `time` and contact columns decoded by `remap_contact`
+    pub fn from_contact(df: &DataFrame) -> Result<Self> {
+        let time = df.column("time")?.f64()?.into_no_null_iter();
+        let time = time.collect::<Vec<f64>>();
+        let mut events = Self::default();
+        for side in Side::BOTH {
+            let column = df.column(side.contact()).map_err(|_| {
+                GaitError::MissingContact {
+                    path: None,
+                    column: side.contact().to_string(),
+                }
+            })?;
+            let contact = column
+                .bool()?
+                .into_iter()
+                .map(|c| c.unwrap_or(false))
+                .collect::<Vec<bool>>();
+            let foot = match side {
+                Side::Left => &mut events.left,
+                Side::Right => &mut events.right,
+            };
+            for (i, pair) in contact.windows(2).enumerate() {
+                match (pair[0], pair[1]) {
+                    (false, true) => foot.initial_contact.push(time[i + 1]),
+                    (true, false) => foot.toe_off.push(time[i + 1]),
+                    _ => {}
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    pub fn foot(&self, side: Side) -> &FootEvents {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    /// side of the first heel strike
+    pub fn leading(&self) -> Option<Side> {
+        match (
+            self.left.initial_contact.first(),
+            self.right.initial_contact.first(),
+        ) {
+            (Some(l), Some(r)) if r < l => Some(Side::Right),
+            (Some(_), _) => Some(Side::Left),
+            (None, Some(_)) => Some(Side::Right),
+            (None, None) => None,
+        }
+    }
+
+    /// gait cycles, strides of the leading foot
+    pub fn gait(&self) -> Result<Vec<Phase>> {
+        let strides = self.leading().map_or(vec![], |s| self.strides(s));
+        if strides.is_empty() {
+            return Err(GaitError::EmptyGait { path: None });
+        }
+        Ok(strides)
+    }
+
+    /// heel strike to next heel strike of the same foot
+    pub fn strides(&self, side: Side) -> Vec<Phase> {
+        self.foot(side)
+            .initial_contact
+            .windows(2)
+            .map(|w| Phase {
+                side,
+                start: w[0],
+                end: w[1],
+            })
+            .collect()
+    }
+
+    /// other foot's heel strike to this foot's one
+    pub fn steps(&self, side: Side) -> Vec<Phase> {
+        let this = &self.foot(side).initial_contact;
+        let other = &self.foot(side.other()).initial_contact;
+        let mut prev = f64::NEG_INFINITY;
+        this.iter()
+            .filter_map(|end| {
+                let start =
+                    other.iter().rev().find(|o| prev < **o && **o < *end);
+                prev = *end;
+                start.map(|start| Phase {
+                    side,
+                    start: *start,
+                    end: *end,
+                })
+            })
+            .collect()
+    }
+
+    /// foot on ground, heel strike to toe-off
+    pub fn stance(&self, side: Side) -> Vec<Phase> {
+        self.phases(side, |this, _| this)
+    }
+
+    /// foot in the air, toe-off to heel strike
+    pub fn swing(&self, side: Side) -> Vec<Phase> {
+        self.phases(side, |this, _| !this)
+    }
+
+    /// only this foot on ground
+    pub fn single_support(&self, side: Side) -> Vec<Phase> {
+        self.phases(side, |this, other| this && !other)
+    }
+
+    /// both feet on ground after this foot's heel strike
+    pub fn double_support(&self, side: Side) -> Vec<Phase> {
+        let strikes = &self.foot(side).initial_contact;
+        self.phases(side, |this, other| this && other)
+            .into_iter()
+            .filter(|p| strikes.contains(&p.start))
+            .collect()
+    }
+
+    /// double support of both feet in time order
+    pub fn double_supports(&self) -> Vec<Phase> {
+        let mut phases = self.double_support(Side::Left);
+        phases.extend(self.double_support(Side::Right));
+        phases.sort_by(|a, b| a.start.total_cmp(&b.start));
+        phases
+    }
+
+    /// intervals between transitions where `state(this, other)` holds,
+    /// intervals not started or ended by a transition are dropped
+    fn phases<F>(&self, side: Side, state: F) -> Vec<Phase>
+    where
+        F: Fn(bool, bool) -> bool,
+    {
+        let (this, other) = (self.foot(side), self.foot(side.other()));
+        let mut times = [this, other]
+            .iter()
+            .flat_map(|f| f.initial_contact.iter().chain(f.toe_off.iter()))
+            .cloned()
+            .collect::<Vec<f64>>();
+        times.sort_by(|a, b| a.total_cmp(b));
+        times.dedup();
+
+        let at = |t: f64| state(this.on_at(t), other.on_at(t));
+        let mut phases = vec![];
+        let mut start = None;
+        let mut prev = at(f64::NEG_INFINITY);
+        for t in times {
+            let now = at(t);
+            match (prev, now, start) {
+                (false, true, _) => start = Some(t),
+                (true, false, Some(s)) => {
+                    phases.push(Phase {
+                        side,
+                        start: s,
+                        end: t,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+            prev = now;
+        }
+        phases
+    }
+}
+
+/// `side`, `start`, `end` columns of phases
+pub fn phases_df(phases: &[Phase]) -> Result<DataFrame> {
+    let sides = phases.iter().map(|p| p.side.to_string());
+    Ok(df!(
+        "side" => sides.collect::<Vec<String>>(),
+        "start" => phases.iter().map(|p| p.start).collect::<Vec<f64>>(),
+        "end" => phases.iter().map(|p| p.end).collect::<Vec<f64>>(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// left on 2-8, 12-18, ..., right on 0-3, 7-13, ..., one sample a
+    /// second
+    fn events() -> GaitEvents {
+        let time = (0..40).map(|i| i as f64).collect::<Vec<f64>>();
+        let left = (0..40).map(|i| (2..8).contains(&(i % 10)));
+        let right = (0..40).map(|i| !(3..7).contains(&(i % 10)));
+        let df = df!(
+            "time" => time,
+            LT_CONTACT => left.collect::<Vec<bool>>(),
+            RT_CONTACT => right.collect::<Vec<bool>>(),
+        )
+        .unwrap();
+        GaitEvents::from_contact(&df).unwrap()
+    }
+
+    fn spans(phases: &[Phase]) -> Vec<(f64, f64)> {
+        phases.iter().map(|p| (p.start, p.end)).collect()
+    }
+
+    #[test]
+    fn contact_transitions() {
+        let events = events();
+        assert_eq!(events.left.initial_contact, vec![2., 12., 22., 32.]);
+        assert_eq!(events.left.toe_off, vec![8., 18., 28., 38.]);
+        assert_eq!(events.right.initial_contact, vec![7., 17., 27., 37.]);
+        assert_eq!(events.right.toe_off, vec![3., 13., 23., 33.]);
+        assert_eq!(events.leading(), Some(Side::Left));
+    }
+
+    #[test]
+    fn gait_is_leading_strides() {
+        let gait = events().gait().unwrap();
+        assert!(gait.iter().all(|p| p.side == Side::Left));
+        assert_eq!(spans(&gait), vec![(2., 12.), (12., 22.), (22., 32.)]);
+        assert_eq!(spans(&events().strides(Side::Right)).len(), 3);
+    }
+
+    #[test]
+    fn steps_need_other_strike() {
+        let events = events();
+        assert_eq!(
+            spans(&events.steps(Side::Right)),
+            vec![(2., 7.), (12., 17.), (22., 27.), (32., 37.)]
+        );
+        // first left strike has no right strike before it
+        assert_eq!(
+            spans(&events.steps(Side::Left)),
+            vec![(7., 12.), (17., 22.), (27., 32.)]
+        );
+    }
+
+    #[test]
+    fn support_phases() {
+        let events = events();
+        assert_eq!(
+            spans(&events.stance(Side::Left)),
+            vec![(2., 8.), (12., 18.), (22., 28.), (32., 38.)]
+        );
+        let single = vec![(3., 7.), (13., 17.), (23., 27.), (33., 37.)];
+        assert_eq!(spans(&events.single_support(Side::Left)), single);
+        assert_eq!(spans(&events.swing(Side::Right)), single);
+        assert_eq!(
+            spans(&events.double_support(Side::Left)),
+            vec![(2., 3.), (12., 13.), (22., 23.), (32., 33.)]
+        );
+        assert_eq!(
+            spans(&events.double_supports())[..3],
+            [(2., 3.), (7., 8.), (12., 13.)]
+        );
+    }
+
+    #[test]
+    fn no_strides() {
+        let df = df!(
+            "time" => [0., 1., 2.],
+            LT_CONTACT => [true, true, true],
+            RT_CONTACT => [false, false, false],
+        )
+        .unwrap();
+        let events = GaitEvents::from_contact(&df).unwrap();
+        assert_eq!(events.leading(), None);
+        assert!(matches!(events.gait(), Err(GaitError::EmptyGait { .. })));
+        let df = df!("time" => [0., 1.]).unwrap();
+        assert!(matches!(
+            GaitEvents::from_contact(&df),
+            Err(GaitError::MissingContact { .. })
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::{GaitError, Result};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub const VERSION_KEY: &str = "exported with version";
+pub const LAST_NAME_KEY: &str = "last_name";
+pub const FIRST_NAME_KEY: &str = "first_name";
+pub const SELECTION_KEY: &str = "selection";
+/// selection strategy written by split, next to `selection`
+pub const STRATEGY_KEY: &str = "strategy";
+
+/// two-line info header on top of every Noraxon recording
+/// first line is keys, second line is values
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingHeader {
+    pub version: String,
+    pub last_name: Option<String>,
+    pub first_name: Option<String>,
+    pub selection: Option<Vec<(f64, f64)>>,
+    /// key/value pairs we don't model, in original order
+    pub extra: Vec<(String, String)>,
+    /// key order as read, used to write header back
+    order: Vec<String>,
+}
+
+impl RecordingHeader {
+    /// read only first two lines of file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(GaitError::io(path))?;
+        Self::from_reader(BufReader::new(file), path)
+    }
+
+    /// parse header from reader, `path` is only for error report
+    pub fn from_reader<R: BufRead>(reader: R, path: &Path) -> Result<Self> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut records = rdr.records().take(2);
+        let mut next_record = || -> Result<Vec<String>> {
+            match records.next() {
+                Some(r) => Ok(r
+                    .map_err(|e| GaitError::io(path)(e.into()))?
+                    .iter()
+                    .map(str::to_string)
+                    .collect()),
+                None => Ok(vec![]),
+            }
+        };
+        let keys = next_record()?;
+        let values = next_record()?;
+        Self::from_pairs(keys.into_iter().zip(values), path)
+    }
+
+    fn from_pairs<I>(pairs: I, path: &Path) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut version = None;
+        let mut last_name = None;
+        let mut first_name = None;
+        let mut selection = None;
+        let mut extra = vec![];
+        let mut order = vec![];
+        for (key, value) in pairs {
+            order.push(key.clone());
+            match key.as_str() {
+                VERSION_KEY => version = Some(value),
+                LAST_NAME_KEY => last_name = Some(value),
+                FIRST_NAME_KEY => first_name = Some(value),
+                SELECTION_KEY => selection = Some(parse_selection(&value)?),
+                _ => extra.push((key, value)),
+            }
+        }
+        let version = version.ok_or_else(|| GaitError::MissingHeaderField {
+            path: path.to_path_buf(),
+            field: VERSION_KEY.to_string(),
+        })?;
+        Ok(Self {
+            version,
+            last_name,
+            first_name,
+            selection,
+            extra,
+            order,
+        })
+    }
+
+    /// get value of any header key
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            VERSION_KEY => Some(self.version.clone()),
+            LAST_NAME_KEY => self.last_name.clone(),
+            FIRST_NAME_KEY => self.first_name.clone(),
+            SELECTION_KEY => self.selection.as_deref().map(format_selection),
+            _ => self
+                .extra
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone()),
+        }
+    }
+
+    /// set value of unmodeled key, new key is appended at the end
+    pub fn set_extra(&mut self, key: &str, value: String) {
+        match self.extra.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.extra.push((key.to_string(), value)),
+        }
+    }
+
+    /// set value of version, name or unmodeled key, selection has to be
+    /// set on its own field
+    pub fn set(&mut self, key: &str, value: String) {
+        match key {
+            VERSION_KEY => self.version = value,
+            LAST_NAME_KEY => self.last_name = Some(value),
+            FIRST_NAME_KEY => self.first_name = Some(value),
+            _ => self.set_extra(key, value),
+        }
+    }
+
+    /// drop any key but version
+    pub fn remove(&mut self, key: &str) {
+        match key {
+            VERSION_KEY => {}
+            LAST_NAME_KEY => self.last_name = None,
+            FIRST_NAME_KEY => self.first_name = None,
+            SELECTION_KEY => self.selection = None,
+            _ => self.extra.retain(|(k, _)| k != key),
+        }
+    }
+
+    /// drop subject name fields
+    pub fn remove_names(&mut self) {
+        self.last_name = None;
+        self.first_name = None;
+    }
+
+    /// key/value pairs in output order
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        let mut keys = self.order.clone();
+        for key in [VERSION_KEY, LAST_NAME_KEY, FIRST_NAME_KEY, SELECTION_KEY]
+            .into_iter()
+            .map(str::to_string)
+            .chain(self.extra.iter().map(|(k, _)| k.clone()))
+        {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys.into_iter()
+            .filter_map(|k| self.get(&k).map(|v| (k, v)))
+            .collect()
+    }
+
+    /// write two header lines, without the blank line before data
+    pub fn write<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let pairs = self.pairs();
+        let mut wtr = csv::WriterBuilder::new()
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_writer(writer);
+        wtr.write_record(pairs.iter().map(|(k, _)| k))?;
+        wtr.write_record(pairs.iter().map(|(_, v)| v))?;
+        wtr.flush()
+    }
+}
+
+/// parse `start-end start-end` selection string
+pub fn parse_selection(value: &str) -> Result<Vec<(f64, f64)>> {
+    value
+        .split_whitespace()
+        .map(|r| {
+            let invalid = || GaitError::InvalidSelection {
+                value: r.to_string(),
+            };
+            let (start, end) = r.split_once('-').ok_or_else(invalid)?;
+            Ok((
+                start.parse::<f64>().map_err(|_| invalid())?,
+                end.parse::<f64>().map_err(|_| invalid())?,
+            ))
+        })
+        .collect()
+}
+
+pub fn format_selection(ranges: &[(f64, f64)]) -> String {
+    ranges
+        .iter()
+        .map(|(s, e)| format!("{}-{}", s, e))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "type,exported with version,last_name,first_name,\
+                          subject,selection\n\
+                          gait,3.18.98,Chen,Wesley,101,4.37-15.965 18.06-22\n";
+
+    fn read(text: &str) -> Result<RecordingHeader> {
+        RecordingHeader::from_reader(text.as_bytes(), Path::new("test.csv"))
+    }
+
+    #[test]
+    fn read_modeled_and_extra_fields() {
+        let header = read(HEADER).unwrap();
+        assert_eq!(header.version, "3.18.98");
+        assert_eq!(header.last_name.as_deref(), Some("Chen"));
+        assert_eq!(header.first_name.as_deref(), Some("Wesley"));
+        assert_eq!(header.selection, Some(vec![(4.37, 15.965), (18.06, 22.)]));
+        assert_eq!(header.get("subject").as_deref(), Some("101"));
+        assert_eq!(header.get("type").as_deref(), Some("gait"));
+    }
+
+    #[test]
+    fn write_round_trip_keeps_key_order() {
+        let header = read(HEADER).unwrap();
+        let mut out = vec![];
+        header.write(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), HEADER);
+        assert_eq!(read(HEADER).unwrap(), header);
+    }
+
+    #[test]
+    fn removed_names_and_new_keys_written() {
+        let mut header = read(HEADER).unwrap();
+        header.remove_names();
+        header.set_extra(STRATEGY_KEY, "centred:70:4".to_string());
+        let mut out = vec![];
+        header.write(&mut out).unwrap();
+        let back = read(std::str::from_utf8(&out).unwrap()).unwrap();
+        assert_eq!(back.last_name, None);
+        assert_eq!(back.first_name, None);
+        assert_eq!(back.get(STRATEGY_KEY).as_deref(), Some("centred:70:4"));
+        assert_eq!(back.selection, header.selection);
+    }
+
+    #[test]
+    fn missing_version_is_error() {
+        let err = read("type,subject\ngait,101\n").unwrap_err();
+        assert!(matches!(err, GaitError::MissingHeaderField { .. }));
+    }
+
+    #[test]
+    fn selection_parse_and_format() {
+        let ranges = parse_selection("1-2.5 3-4").unwrap();
+        assert_eq!(ranges, vec![(1., 2.5), (3., 4.)]);
+        assert_eq!(format_selection(&ranges), "1-2.5 3-4");
+        assert!(parse_selection("1-x").is_err());
+        assert!(parse_selection("12").is_err());
+    }
+}
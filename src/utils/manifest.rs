@@ -0,0 +1,293 @@
+use crate::error::{GaitError, Result};
+use crate::utils::util::hex;
+
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// hidden manifest file of `command` in output dir
+pub fn manifest_path(dir: &Path, command: &str) -> PathBuf {
+    dir.join(format!(".{}-manifest.json", command))
+}
+
+/// size, modified time and content hash of an input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stamp {
+    pub size: u64,
+    /// nanoseconds since unix epoch
+    pub mtime: u128,
+    /// sha256 of content in hex
+    pub hash: String,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Result<Self> {
+        let (size, mtime) = size_mtime(path)?;
+        let mut file =
+            BufReader::new(File::open(path).map_err(GaitError::io(path))?);
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(GaitError::io(path))?;
+        Ok(Self {
+            size,
+            mtime,
+            hash: hex(&hasher.finalize()),
+        })
+    }
+
+    /// one stamp over named stamps, for an output built from many inputs
+    pub fn combine(stamps: &[(String, Stamp)]) -> Self {
+        let mut hasher = Sha256::new();
+        for (name, stamp) in stamps {
+            hasher.update(name.as_bytes());
+            hasher.update(stamp.hash.as_bytes());
+        }
+        Self {
+            size: stamps.iter().map(|(_, s)| s.size).sum(),
+            mtime: stamps.iter().map(|(_, s)| s.mtime).max().unwrap_or(0),
+            hash: hex(&hasher.finalize()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "size": self.size,
+            "mtime": self.mtime.to_string(),
+            "hash": self.hash,
+        })
+    }
+
+    fn from_json(v: &Value) -> Option<Self> {
+        Some(Self {
+            size: v["size"].as_u64()?,
+            mtime: v["mtime"].as_str()?.parse().ok()?,
+            hash: v["hash"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// last processed stamp and outputs of one input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub stamp: Stamp,
+    /// output files relative to output dir
+    pub outputs: Vec<String>,
+}
+
+/// inputs processed into an output dir, with tool version and parameters
+/// they were processed with, an input is up to date when neither it nor
+/// the parameters changed and its outputs still exist
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub command: String,
+    pub version: String,
+    pub params: Value,
+    /// keyed by input path relative to input dir
+    pub entries: BTreeMap<String, Entry>,
+}
+
+impl Manifest {
+    /// manifest of `command` in `dir`, emptied if version or `params`
+    /// changed
+    pub fn open(dir: &Path, command: &str, params: Value) -> Self {
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        let entries = fs::read_to_string(manifest_path(dir, command))
+            .ok()
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .filter(|m| {
+                m["version"] == version.as_str() && m["params"] == params
+            })
+            .and_then(|m| m["inputs"].as_object().map(read_entries))
+            .unwrap_or_default();
+        Self {
+            command: command.to_string(),
+            version,
+            params,
+            entries,
+        }
+    }
+
+    /// stamp of `path` and whether `key` is up to date with it, content
+    /// is only hashed again when size or modified time changed
+    pub fn check(
+        &self,
+        dir: &Path,
+        key: &str,
+        path: &Path,
+    ) -> Result<(bool, Stamp)> {
+        let entry = self.entries.get(key);
+        let stamp = match entry {
+            Some(e) if (e.stamp.size, e.stamp.mtime) == size_mtime(path)? => {
+                e.stamp.clone()
+            }
+            _ => Stamp::of(path)?,
+        };
+        Ok((self.is_fresh(dir, key, &stamp), stamp))
+    }
+
+    /// `key` was processed from `stamp` and its outputs exist
+    pub fn is_fresh(&self, dir: &Path, key: &str, stamp: &Stamp) -> bool {
+        match self.entries.get(key) {
+            Some(e) => {
+                e.stamp.hash == stamp.hash
+                    && e.outputs.iter().all(|o| dir.join(o).is_file())
+            }
+            None => false,
+        }
+    }
+
+    pub fn record(&mut self, key: &str, stamp: Stamp, outputs: Vec<String>) {
+        self.entries
+            .insert(key.to_string(), Entry { stamp, outputs });
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).map_err(GaitError::io(dir))?;
+        let inputs = self
+            .entries
+            .iter()
+            .map(|(k, e)| {
+                let mut v = e.stamp.to_json();
+                v["outputs"] = json!(e.outputs);
+                (k.clone(), v)
+            })
+            .collect::<Map<String, Value>>();
+        let manifest = json!({
+            "version": self.version,
+            "params": self.params,
+            "inputs": inputs,
+        });
+        let path = manifest_path(dir, &self.command);
+        let text = serde_json::to_string_pretty(&manifest)
+            .expect("json value is always serializable");
+        fs::write(&path, text).map_err(GaitError::io(&path))
+    }
+}
+
+fn read_entries(inputs: &Map<String, Value>) -> BTreeMap<String, Entry> {
+    inputs
+        .iter()
+        .filter_map(|(k, v)| {
+            let outputs = v["outputs"]
+                .as_array()?
+                .iter()
+                .map(|o| o.as_str().map(str::to_string))
+                .collect::<Option<Vec<String>>>()?;
+            Some((
+                k.clone(),
+                Entry {
+                    stamp: Stamp::from_json(v)?,
+                    outputs,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn size_mtime(path: &Path) -> Result<(u64, u128)> {
+    let meta = fs::metadata(path).map_err(GaitError::io(path))?;
+    let mtime = meta
+        .modified()
+        .map_err(GaitError::io(path))?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// fresh dir with input `a.csv` and its output `out/a.csv`
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-manifest-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("a.csv"), "abc").unwrap();
+        fs::write(dir.join("out/a.csv"), "").unwrap();
+        dir
+    }
+
+    /// manifest with `a.csv` recorded, saved to `out`
+    fn saved(dir: &Path) -> Manifest {
+        let mut manifest = Manifest::open(dir, "split", json!({"p": 1}));
+        let stamp = Stamp::of(&dir.join("a.csv")).unwrap();
+        manifest.record("a.csv", stamp, vec!["a.csv".to_string()]);
+        manifest.save(&dir.join("out")).unwrap();
+        manifest
+    }
+
+    #[test]
+    fn stamp_hashes_content() {
+        let dir = test_dir("stamp");
+        let stamp = Stamp::of(&dir.join("a.csv")).unwrap();
+        assert_eq!(stamp.size, 3);
+        assert_eq!(
+            stamp.hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn save_open_round_trip() {
+        let dir = test_dir("round-trip");
+        let manifest = saved(&dir);
+        let out = dir.join("out");
+        assert!(manifest_path(&out, "split").is_file());
+        let opened = Manifest::open(&out, "split", json!({"p": 1}));
+        assert_eq!(opened, manifest);
+        // manifests of other commands are separate
+        assert!(Manifest::open(&out, "export", json!({"p": 1}))
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn open_drops_entries_on_change() {
+        let dir = test_dir("invalidate");
+        saved(&dir);
+        let out = dir.join("out");
+        let opened = Manifest::open(&out, "split", json!({"p": 2}));
+        assert!(opened.entries.is_empty());
+        assert_eq!(opened.params, json!({"p": 2}));
+
+        let path = manifest_path(&out, "split");
+        let text = fs::read_to_string(&path).unwrap();
+        let old = text.replace(env!("CARGO_PKG_VERSION"), "0.0.0");
+        fs::write(&path, old).unwrap();
+        let opened = Manifest::open(&out, "split", json!({"p": 1}));
+        assert!(opened.entries.is_empty());
+        assert_eq!(opened.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn fresh_until_input_or_output_changes() {
+        let dir = test_dir("fresh");
+        let manifest = saved(&dir);
+        let (out, input) = (dir.join("out"), dir.join("a.csv"));
+        let stamp = Stamp::of(&input).unwrap();
+        assert!(manifest.is_fresh(&out, "a.csv", &stamp));
+        assert!(!manifest.is_fresh(&out, "b.csv", &stamp));
+        assert!(manifest.check(&out, "a.csv", &input).unwrap().0);
+
+        fs::write(&input, "abcd").unwrap();
+        let (fresh, changed) = manifest.check(&out, "a.csv", &input).unwrap();
+        assert!(!fresh);
+        assert_ne!(changed.hash, stamp.hash);
+
+        fs::remove_file(out.join("a.csv")).unwrap();
+        assert!(!manifest.is_fresh(&out, "a.csv", &stamp));
+    }
+}
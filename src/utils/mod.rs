@@ -1,2 +1,21 @@
+pub mod batch;
+pub mod bouts;
+pub mod channel;
+pub mod contact;
+pub mod deid;
+pub mod discover;
+pub mod events;
+pub mod gait;
+pub mod header;
+pub mod manifest;
+pub mod noraxon;
+pub mod normalize;
 pub mod preprocess;
+pub mod profile;
+pub mod provenance;
+pub mod quality;
+pub mod recording;
+pub mod remap;
+pub mod selection;
+pub mod spatiotemporal;
 pub mod util;
@@ -0,0 +1,368 @@
+use crate::error::{GaitError, Result};
+use crate::utils::header::RecordingHeader;
+use crate::utils::remap::{
+    resolve_remap, Language, RemapChoice, RemapReason, RemapTable,
+};
+
+use polars::prelude::*;
+use std::fs::{remove_file, rename, File};
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+/// columns polars may infer as int but actually contain float
+const FLOAT_COLUMNS: [&str; 2] = [
+    "Noraxon MyoMotion-Joints-Shoulder RT-Abduction Hrz (deg)",
+    "Noraxon MyoMotion-Joints-Shoulder LT-Abduction Hrz (deg)",
+];
+
+/// one recording read from disk
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub data: DataFrame,
+    /// remap tables applied to data, if any
+    pub remap: Option<RemapChoice>,
+}
+
+impl Recording {
+    /// selection ranges stored in header, empty if not selected yet
+    pub fn selection(&self) -> &[(f64, f64)] {
+        self.header.selection.as_deref().unwrap_or(&[])
+    }
+}
+
+/// read header and data of Noraxon csv in one pass
+pub struct NoraxonReader {
+    path: PathBuf,
+    remap_dir: Option<PathBuf>,
+    remap_tables: Vec<PathBuf>,
+    language_tables: Vec<(Language, PathBuf)>,
+}
+
+impl NoraxonReader {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            remap_dir: None,
+            remap_tables: vec![],
+            language_tables: vec![],
+        }
+    }
+
+    /// remap columns with table resolved from dir by export version
+    pub fn with_remap_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.remap_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// remap columns with given table, tables added earlier have higher
+    /// priority and all given tables win over the one resolved from dir
+    pub fn with_remap_table<P: AsRef<Path>>(mut self, table: P) -> Self {
+        self.remap_tables.push(table.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn with_remap_tables<P: AsRef<Path>>(mut self, tables: &[P]) -> Self {
+        for table in tables {
+            self = self.with_remap_table(table);
+        }
+        self
+    }
+
+    /// remap columns with given table only when column names are in
+    /// `language`, after tables given without language
+    pub fn with_language_table<P: AsRef<Path>>(
+        mut self,
+        language: Language,
+        table: P,
+    ) -> Self {
+        self.language_tables
+            .push((language, table.as_ref().to_path_buf()));
+        self
+    }
+
+    pub fn finish(self) -> Result<Recording> {
+        let path = &self.path;
+        let mut bytes = std::fs::read(path).map_err(GaitError::io(path))?;
+        let data_start = find_data_start(&bytes);
+        let header = RecordingHeader::from_reader(&bytes[..data_start], path)?;
+
+        bytes.drain(..data_start);
+        let schema = Schema::from(
+            FLOAT_COLUMNS
+                .iter()
+                .map(|c| Field::new(c, DataType::Float64)),
+        );
+        let mut data = CsvReader::new(Cursor::new(bytes))
+            .with_dtypes(Some(&schema))
+            .finish()?;
+
+        let mut tables = self.remap_tables;
+        let language = Language::detect(&data.get_column_names());
+        tables.extend(
+            self.language_tables
+                .into_iter()
+                .filter(|(l, _)| *l == language)
+                .map(|(_, table)| table),
+        );
+        let remap = match self.remap_dir {
+            Some(dir) => {
+                let mut choice = resolve_remap(
+                    &dir,
+                    &header.version,
+                    &data.get_column_names(),
+                )?;
+                choice.extra = tables.clone();
+                tables.push(choice.table.clone());
+                Some(choice)
+            }
+            None if tables.is_empty() => None,
+            None => Some(RemapChoice {
+                table: tables[0].clone(),
+                reason: RemapReason::Given,
+                extra: tables[1..].to_vec(),
+            }),
+        };
+        if remap.is_some() {
+            let table = RemapTable::merge(&tables)?;
+            // file already remapped only has new names
+            let news = table.news();
+            let remapped = data
+                .get_column_names()
+                .iter()
+                .all(|c| news.iter().any(|n| n == c));
+            if !remapped {
+                data = table.apply(&data).map_err(|e| e.in_file(path))?;
+            }
+        }
+
+        Ok(Recording {
+            header,
+            data,
+            remap,
+        })
+    }
+}
+
+/// write header and data into a temp file next to target, then rename it
+pub struct NoraxonWriter {
+    path: PathBuf,
+}
+
+impl NoraxonWriter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn finish(
+        &self,
+        header: &RecordingHeader,
+        data: &mut DataFrame,
+    ) -> Result<()> {
+        let tmp_path = self.tmp_path()?;
+        match Self::write(&tmp_path, header, data) {
+            Ok(()) => rename(&tmp_path, &self.path).map_err(|e| {
+                let _ = remove_file(&tmp_path);
+                GaitError::io(&self.path)(e)
+            }),
+            Err(e) => {
+                let _ = remove_file(&tmp_path);
+                Err(e)
+            }
+        }
+    }
+
+    /// `.<filename>.tmp` in the same directory, so rename never cross disks
+    fn tmp_path(&self) -> Result<PathBuf> {
+        let filename =
+            self.path.file_name().and_then(|n| n.to_str()).ok_or_else(
+                || GaitError::FileName {
+                    path: self.path.clone(),
+                    reason: "no valid file name".to_string(),
+                },
+            )?;
+        Ok(self.path.with_file_name(format!(".{}.tmp", filename)))
+    }
+
+    fn write(
+        path: &Path,
+        header: &RecordingHeader,
+        data: &mut DataFrame,
+    ) -> Result<()> {
+        let file = File::create(path).map_err(GaitError::io(path))?;
+        let mut writer = BufWriter::new(file);
+        header.write(&mut writer).map_err(GaitError::io(path))?;
+        writeln!(writer).map_err(GaitError::io(path))?;
+        CsvWriter::new(&mut writer)
+            .has_header(true)
+            .with_delimiter(b',')
+            .finish(data)?;
+        let file = writer
+            .into_inner()
+            .map_err(|e| GaitError::io(path)(e.into_error()))?;
+        file.sync_all().map_err(GaitError::io(path))
+    }
+}
+
+/// byte offset right after the blank line separating header and data
+fn find_data_start(bytes: &[u8]) -> usize {
+    let mut offset = 0;
+    for line in bytes.split_inclusive(|b| *b == b'\n') {
+        offset += line.len();
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return offset;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const RECORDING: &str = "type,exported with version\n\
+                             gait,3.18.98\n\
+                             \n\
+                             time,a\n\
+                             0,1.5\n\
+                             0.01,2.5\n";
+
+    /// fresh dir for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-noraxon-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn data_starts_after_blank_line() {
+        assert_eq!(find_data_start(b"a,b\n1,2\n\ntime\n0\n"), 9);
+        assert_eq!(find_data_start(b"a,b\r\n1,2\r\n\r\ntime\r\n"), 12);
+        assert_eq!(find_data_start(b"a,b\n1,2\n \t\ntime\n"), 11);
+        // no header at all
+        assert_eq!(find_data_start(b"time,a\n0,1\n"), 0);
+    }
+
+    #[test]
+    fn read_header_and_data() {
+        let dir = test_dir("read");
+        let path = dir.join("rec.csv");
+        fs::write(&path, RECORDING).unwrap();
+        let recording = NoraxonReader::new(&path).finish().unwrap();
+        assert_eq!(recording.header.version, "3.18.98");
+        assert_eq!(recording.header.get("type").as_deref(), Some("gait"));
+        assert_eq!(recording.data.get_column_names(), ["time", "a"]);
+        assert_eq!(recording.data.height(), 2);
+        assert_eq!(recording.remap, None);
+    }
+
+    #[test]
+    fn given_table_beats_resolved_one() {
+        let dir = test_dir("priority");
+        let path = dir.join("rec.csv");
+        fs::write(&path, RECORDING).unwrap();
+        fs::write(dir.join("3.18.98.csv"), "Original,New\ntime,time\na,dir\n")
+            .unwrap();
+        let given = dir.join("given.json");
+        fs::write(&given, r#"{"a": "given"}"#).unwrap();
+
+        let recording = NoraxonReader::new(&path)
+            .with_remap_dir(&dir)
+            .with_remap_table(&given)
+            .finish()
+            .unwrap();
+        assert_eq!(recording.data.get_column_names(), ["given", "time"]);
+        let remap = recording.remap.unwrap();
+        assert_eq!(remap.table, dir.join("3.18.98.csv"));
+        assert_eq!(remap.reason, RemapReason::Exact);
+        assert_eq!(remap.extra, vec![given]);
+    }
+
+    /// name.json of assets, it remaps Chinese exports only
+    fn name_json() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/name.json")
+    }
+
+    #[test]
+    fn language_table_skips_other_language() {
+        let dir = test_dir("english");
+        let path = dir.join("rec.csv");
+        // split output keeps English names name.json doesn't produce
+        let columns = "time,Ultium Motion.Switch 1 (On),L Accel Sensor X (mG)";
+        let text = format!(
+            "exported with version,selection\n3.18.98,0-1\n\n{}\n0,1,2\n",
+            columns
+        );
+        fs::write(&path, text).unwrap();
+        let recording = NoraxonReader::new(&path)
+            .with_language_table(Language::Chinese, name_json())
+            .finish()
+            .unwrap();
+        assert_eq!(recording.data.get_column_names().join(","), columns);
+        assert_eq!(recording.remap, None);
+    }
+
+    #[test]
+    fn language_table_remaps_its_language() {
+        let table = RemapTable::load(name_json()).unwrap();
+        let originals = table.originals();
+        let dir = test_dir("chinese");
+        let path = dir.join("rec.csv");
+        let zeros = vec!["0"; originals.len()].join(",");
+        let text = format!(
+            "exported with version\n3.18.98\n\n{}\n{}\n",
+            originals.join(","),
+            zeros
+        );
+        fs::write(&path, text).unwrap();
+        let recording = NoraxonReader::new(&path)
+            .with_language_table(Language::Chinese, name_json())
+            .finish()
+            .unwrap();
+        assert_eq!(recording.data.get_column_names(), table.news());
+        assert_eq!(recording.remap.unwrap().reason, RemapReason::Given);
+    }
+
+    #[test]
+    fn write_replaces_through_temp_file() {
+        let dir = test_dir("write");
+        let src = dir.join("rec.csv");
+        fs::write(&src, RECORDING).unwrap();
+        let mut recording = NoraxonReader::new(&src).finish().unwrap();
+
+        let out = dir.join("out.csv");
+        let writer = NoraxonWriter::new(&out);
+        writer
+            .finish(&recording.header, &mut recording.data)
+            .unwrap();
+        assert!(!dir.join(".out.csv.tmp").exists());
+        let back = NoraxonReader::new(&out).finish().unwrap();
+        assert_eq!(back.header, recording.header);
+        assert_eq!(back.data, recording.data);
+    }
+
+    #[test]
+    fn failed_write_keeps_destination() {
+        let dir = test_dir("fail");
+        let src = dir.join("rec.csv");
+        fs::write(&src, RECORDING).unwrap();
+        let mut recording = NoraxonReader::new(&src).finish().unwrap();
+
+        let out = dir.join("out.csv");
+        fs::write(&out, "old").unwrap();
+        // temp file can't be created where a dir is
+        fs::create_dir(dir.join(".out.csv.tmp")).unwrap();
+        let writer = NoraxonWriter::new(&out);
+        assert!(writer
+            .finish(&recording.header, &mut recording.data)
+            .is_err());
+        assert_eq!(fs::read_to_string(&out).unwrap(), "old");
+    }
+}
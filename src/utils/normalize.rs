@@ -0,0 +1,181 @@
+use crate::error::Result;
+use crate::utils::gait::Phase;
+use crate::utils::spatiotemporal::Stat;
+
+use polars::prelude::*;
+
+/// samples of one gait cycle, 0% to 100% in 1% steps
+pub const CYCLE_POINTS: usize = 101;
+
+/// one channel resampled over gait cycle percentage
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleCurves {
+    pub channel: String,
+    /// one curve per stride, in stride order
+    pub strides: Vec<Vec<f64>>,
+    pub mean: Vec<f64>,
+    pub sd: Vec<f64>,
+}
+
+/// cut every stride out of numeric channels and resample to
+/// `CYCLE_POINTS` by linear interpolation on `time`
+pub fn normalize_cycles(
+    df: &DataFrame,
+    strides: &[Phase],
+) -> Result<Vec<CycleCurves>> {
+    let time = df
+        .column("time")?
+        .cast(&DataType::Float64)?
+        .f64()?
+        .into_no_null_iter()
+        .collect::<Vec<f64>>();
+    let mut curves = vec![];
+    for series in df.get_columns() {
+        if series.name() == "time" || !series.dtype().is_numeric() {
+            continue;
+        }
+        let values = series
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .map(|v| v.unwrap_or(f64::NAN))
+            .collect::<Vec<f64>>();
+        let strides = strides
+            .iter()
+            .map(|s| resample(&time, &values, s))
+            .collect::<Vec<Vec<f64>>>();
+        let stats = (0..CYCLE_POINTS)
+            .map(|i| {
+                Stat::new(&strides.iter().map(|c| c[i]).collect::<Vec<_>>())
+            })
+            .collect::<Vec<Stat>>();
+        curves.push(CycleCurves {
+            channel: series.name().to_string(),
+            mean: stats.iter().map(|s| s.mean).collect(),
+            sd: stats.iter().map(|s| s.sd).collect(),
+            strides,
+        });
+    }
+    Ok(curves)
+}
+
+/// long format with `channel`, `curve` (`mean`, `sd` or 1-based stride
+/// number), `percent` and `value` columns
+pub fn cycles_df(curves: &[CycleCurves]) -> Result<DataFrame> {
+    let mut channel = vec![];
+    let mut curve = vec![];
+    let mut percent = vec![];
+    let mut value = vec![];
+    for c in curves {
+        let named = [("mean".to_string(), &c.mean), ("sd".to_string(), &c.sd)];
+        let numbered = c
+            .strides
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ((i + 1).to_string(), s));
+        for (name, values) in named.into_iter().chain(numbered) {
+            for (p, v) in values.iter().enumerate() {
+                channel.push(c.channel.as_str());
+                curve.push(name.clone());
+                percent.push(p as u32);
+                value.push(*v);
+            }
+        }
+    }
+    Ok(df!(
+        "channel" => channel,
+        "curve" => curve,
+        "percent" => percent,
+        "value" => value,
+    )?)
+}
+
+/// values at `CYCLE_POINTS` evenly spaced times of stride
+fn resample(time: &[f64], values: &[f64], stride: &Phase) -> Vec<f64> {
+    (0..CYCLE_POINTS)
+        .map(|i| {
+            let ratio = i as f64 / (CYCLE_POINTS - 1) as f64;
+            let t = stride.start + stride.duration() * ratio;
+            // first sample at or after t
+            let j = time.partition_point(|s| *s < t);
+            match j {
+                0 => values[0],
+                j if j == time.len() => values[j - 1],
+                j if time[j] == t => values[j],
+                j => {
+                    let w = (t - time[j - 1]) / (time[j] - time[j - 1]);
+                    values[j - 1] + (values[j] - values[j - 1]) * w
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::gait::Side;
+
+    fn stride(start: f64, end: f64) -> Phase {
+        Phase {
+            side: Side::Left,
+            start,
+            end,
+        }
+    }
+
+    /// `a` is ten times `time`, one sample a second
+    fn ramp() -> DataFrame {
+        let time = (0..=10).map(|i| i as f64).collect::<Vec<f64>>();
+        let a = time.iter().map(|t| t * 10.).collect::<Vec<f64>>();
+        df!("time" => time, "a" => a).unwrap()
+    }
+
+    fn close(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9)
+    }
+
+    #[test]
+    fn resample_to_cycle_points() {
+        let curves = normalize_cycles(&ramp(), &[stride(0., 10.)]).unwrap();
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0].channel, "a");
+        // 1% of a 10 s stride is 0.1 s, interpolated between samples
+        let expected = (0..CYCLE_POINTS).map(|i| i as f64).collect::<Vec<_>>();
+        assert!(close(&curves[0].strides[0], &expected));
+        assert!(close(&curves[0].mean, &expected));
+    }
+
+    #[test]
+    fn mean_and_sd_over_strides() {
+        let strides = [stride(0., 4.), stride(4., 8.)];
+        let curves = normalize_cycles(&ramp(), &strides).unwrap();
+        assert_eq!(curves[0].strides.len(), 2);
+        assert!(close(&curves[0].strides[1][..1], &[40.]));
+        assert!(close(&curves[0].mean[..1], &[20.]));
+        assert!(curves[0].sd.iter().all(|sd| *sd > 0.));
+    }
+
+    #[test]
+    fn single_sample_cycle() {
+        let df = df!("time" => [0.], "a" => [5.]).unwrap();
+        let curves = normalize_cycles(&df, &[stride(0., 1.)]).unwrap();
+        assert!(close(&curves[0].strides[0], &[5.; CYCLE_POINTS]));
+        // only one sample inside stride, neighbours are interpolated
+        let curves = normalize_cycles(&ramp(), &[stride(4.5, 5.5)]).unwrap();
+        assert!(close(&curves[0].strides[0][..1], &[45.]));
+        assert!(close(&curves[0].strides[0][50..51], &[50.]));
+    }
+
+    #[test]
+    fn long_format() {
+        let strides = [stride(0., 4.), stride(4., 8.)];
+        let curves = normalize_cycles(&ramp(), &strides).unwrap();
+        let df = cycles_df(&curves).unwrap();
+        // mean, sd and two strides
+        assert_eq!(df.height(), 4 * CYCLE_POINTS);
+        let curve = df.column("curve").unwrap().utf8().unwrap();
+        let names = curve.into_no_null_iter().step_by(CYCLE_POINTS);
+        assert_eq!(names.collect::<Vec<_>>(), ["mean", "sd", "1", "2"]);
+    }
+}
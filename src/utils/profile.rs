@@ -0,0 +1,119 @@
+use crate::error::{GaitError, Result};
+
+use polars::prelude::DataFrame;
+use std::path::Path;
+
+/// suffixes export puts on channel stats
+const STAT_SUFFIXES: [&str; 2] = ["_max", "_min"];
+
+/// named list of columns to keep, e.g. `assets/filter.csv`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnProfile {
+    /// file stem of profile
+    pub name: String,
+    /// columns in output order
+    pub columns: Vec<String>,
+}
+
+impl ColumnProfile {
+    /// load single column csv, header line is skipped
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| GaitError::io(path)(e.into()))?;
+        let mut columns = vec![];
+        for record in rdr.records() {
+            let record = record.map_err(|e| GaitError::io(path)(e.into()))?;
+            match record.get(0) {
+                Some(c) if !c.is_empty() => columns.push(c.to_string()),
+                _ => {}
+            }
+        }
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(Self { name, columns })
+    }
+
+    /// same profile with columns not listed put first, in given order
+    pub fn with_leading(&self, columns: &[&str]) -> Self {
+        let mut profile = self.clone();
+        let missing = columns
+            .iter()
+            .filter(|c| !self.columns.iter().any(|p| p == *c))
+            .map(|c| c.to_string());
+        profile.columns.splice(0..0, missing);
+        profile
+    }
+
+    /// select profile columns in profile order
+    pub fn apply(&self, df: &DataFrame) -> Result<DataFrame> {
+        let names = df.get_column_names();
+        if let Some(missing) =
+            self.columns.iter().find(|c| !names.contains(&c.as_str()))
+        {
+            return Err(GaitError::MissingProfileColumn {
+                path: None,
+                profile: self.name.clone(),
+                column: missing.clone(),
+            });
+        }
+        Ok(df.select(&self.columns)?)
+    }
+
+    /// keep profile channels of export result rows, e.g. a cohort table,
+    /// where a channel `c` comes with `c_max`, `c_min` and so on, other
+    /// columns like file info and spatio-temporal stats are always kept
+    pub fn apply_results(&self, df: &DataFrame) -> Result<DataFrame> {
+        let names = df.get_column_names();
+        let base = |name: &str| {
+            let mut base = name;
+            while let Some(b) =
+                STAT_SUFFIXES.iter().find_map(|s| base.strip_suffix(s))
+            {
+                base = b;
+            }
+            base.len()
+        };
+        let is_channel = |c: &str| {
+            names.contains(&c) && names.contains(&format!("{}_max", c).as_str())
+        };
+        if let Some(missing) = self
+            .columns
+            .iter()
+            .filter(|c| c.as_str() != "time")
+            .find(|c| !is_channel(c))
+        {
+            return Err(GaitError::MissingProfileColumn {
+                path: None,
+                profile: self.name.clone(),
+                column: missing.clone(),
+            });
+        }
+
+        /* info columns in place, then every suffix of profile channels
+        in profile order */
+        let mut columns = vec![];
+        let mut suffixes: Vec<&str> = vec![];
+        for name in &names {
+            let (channel, suffix) = name.split_at(base(name));
+            if !is_channel(channel) {
+                columns.push(name.to_string());
+            } else if !suffixes.contains(&suffix) {
+                suffixes.push(suffix);
+            }
+        }
+        for suffix in suffixes {
+            for channel in self.columns.iter().filter(|c| is_channel(c)) {
+                let name = format!("{}{}", channel, suffix);
+                if names.contains(&name.as_str()) {
+                    columns.push(name);
+                }
+            }
+        }
+        Ok(df.select(&columns)?)
+    }
+}
@@ -0,0 +1,297 @@
+use crate::error::{GaitError, Result};
+use crate::utils::header::{RecordingHeader, STRATEGY_KEY};
+use crate::utils::manifest::Stamp;
+use crate::utils::remap::RemapChoice;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const TOOL_VERSION_KEY: &str = "gaitool version";
+pub const COMMAND_KEY: &str = "gaitool command";
+pub const PARAMS_KEY: &str = "gaitool params";
+pub const REMAP_TABLE_KEY: &str = "remap table";
+pub const REMAP_HASH_KEY: &str = "remap hash";
+pub const INPUT_HASH_KEY: &str = "input hash";
+pub const PROCESSED_AT_KEY: &str = "processed at";
+
+/// separates several remap tables in one header value
+const LIST_SEP: &str = ";";
+
+/// how an output was made, in header rows of recordings and in a
+/// `.provenance.json` sidecar next to tabular outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub version: String,
+    pub command: String,
+    /// settings that change output
+    pub params: Value,
+    /// selection strategy of split, see `SelectionStrategy::describe`
+    pub strategy: Option<String>,
+    /// remap tables applied, file name and sha256, base table first
+    pub remap: Vec<(String, String)>,
+    /// input name and sha256
+    pub inputs: Vec<(String, String)>,
+    /// utc, rfc 3339
+    pub processed_at: String,
+}
+
+impl Provenance {
+    /// provenance of `command` run now by this version
+    pub fn new(command: &str, params: Value) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            command: command.to_string(),
+            params,
+            strategy: None,
+            remap: vec![],
+            inputs: vec![],
+            processed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    pub fn strategy(mut self, strategy: Option<String>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// add input `path` by content hash, recorded as `name`
+    pub fn input(mut self, name: &str, path: &Path) -> Result<Self> {
+        self.inputs.push((name.to_string(), Stamp::of(path)?.hash));
+        Ok(self)
+    }
+
+    /// add remap tables of `choice` by content hash
+    pub fn remap(mut self, choice: &RemapChoice) -> Result<Self> {
+        for table in std::iter::once(&choice.table).chain(&choice.extra) {
+            self.remap.push((file_name(table), Stamp::of(table)?.hash));
+        }
+        Ok(self)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let pairs = |v: &[(String, String)], key: &str| {
+            v.iter()
+                .map(|(name, hash)| json!({ key: name, "sha256": hash }))
+                .collect::<Vec<Value>>()
+        };
+        json!({
+            "version": self.version,
+            "command": self.command,
+            "params": self.params,
+            "strategy": self.strategy,
+            "remap": pairs(&self.remap, "table"),
+            "inputs": pairs(&self.inputs, "file"),
+            "processed_at": self.processed_at,
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Option<Self> {
+        let pairs = |v: &Value, key: &str| {
+            v.as_array()?
+                .iter()
+                .map(|p| {
+                    Some((
+                        p[key].as_str()?.to_string(),
+                        p["sha256"].as_str()?.to_string(),
+                    ))
+                })
+                .collect::<Option<Vec<(String, String)>>>()
+        };
+        Some(Self {
+            version: v["version"].as_str()?.to_string(),
+            command: v["command"].as_str()?.to_string(),
+            params: v["params"].clone(),
+            strategy: v["strategy"].as_str().map(str::to_string),
+            remap: pairs(&v["remap"], "table")?,
+            inputs: pairs(&v["inputs"], "file")?,
+            processed_at: v["processed_at"].as_str()?.to_string(),
+        })
+    }
+
+    /// write into header keys, strategy is left to its own header key
+    pub fn write_header(&self, header: &mut RecordingHeader) {
+        let join = |v: &[(String, String)], hash: bool| {
+            v.iter()
+                .map(|(name, h)| if hash { h.clone() } else { name.clone() })
+                .collect::<Vec<String>>()
+                .join(LIST_SEP)
+        };
+        header.set_extra(TOOL_VERSION_KEY, self.version.clone());
+        header.set_extra(COMMAND_KEY, self.command.clone());
+        header.set_extra(PARAMS_KEY, self.params.to_string());
+        header.set_extra(REMAP_TABLE_KEY, join(&self.remap, false));
+        header.set_extra(REMAP_HASH_KEY, join(&self.remap, true));
+        header.set_extra(INPUT_HASH_KEY, join(&self.inputs, true));
+        header.set_extra(PROCESSED_AT_KEY, self.processed_at.clone());
+    }
+
+    /// read back header keys of recording at `path`, none if it wasn't
+    /// written by a gaitool recording provenance
+    pub fn from_header(header: &RecordingHeader, path: &Path) -> Option<Self> {
+        let split = |key: &str| {
+            header
+                .get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| {
+                    v.split(LIST_SEP).map(str::to_string).collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        let params = header.get(PARAMS_KEY)?;
+        let input = file_name(path);
+        Some(Self {
+            version: header.get(TOOL_VERSION_KEY)?,
+            command: header.get(COMMAND_KEY)?,
+            params: serde_json::from_str(&params)
+                .unwrap_or(Value::String(params)),
+            strategy: header.get(STRATEGY_KEY),
+            remap: split(REMAP_TABLE_KEY)
+                .into_iter()
+                .zip(split(REMAP_HASH_KEY))
+                .collect(),
+            inputs: split(INPUT_HASH_KEY)
+                .into_iter()
+                .map(|hash| (input.clone(), hash))
+                .collect(),
+            processed_at: header.get(PROCESSED_AT_KEY)?,
+        })
+    }
+
+    /// write sidecar of `output`
+    pub fn write_sidecar(&self, output: &Path) -> Result<()> {
+        let path = sidecar_path(output);
+        let text = serde_json::to_string_pretty(&self.to_json())
+            .expect("json value is always serializable");
+        fs::write(&path, text).map_err(GaitError::io(&path))
+    }
+
+    /// provenance of `path` from its sidecar, or from its header when it
+    /// has no sidecar, `path` may be the sidecar itself
+    pub fn read(path: &Path) -> Result<Self> {
+        let invalid = |reason: String| GaitError::InvalidProvenance {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let sidecar = if is_sidecar(path) {
+            path.to_path_buf()
+        } else {
+            sidecar_path(path)
+        };
+        if sidecar.is_file() {
+            let text = fs::read_to_string(&sidecar)
+                .map_err(GaitError::io(&sidecar))?;
+            let v = serde_json::from_str::<Value>(&text)
+                .map_err(|e| invalid(e.to_string()))?;
+            return Self::from_json(&v)
+                .ok_or_else(|| invalid("missing or malformed field".into()));
+        }
+        if !path.is_file() {
+            return Err(GaitError::not_found(path));
+        }
+        let header = RecordingHeader::read(path)
+            .map_err(|_| invalid("no sidecar or recording header".into()))?;
+        Self::from_header(&header, path)
+            .ok_or_else(|| invalid(format!("no '{}' in header", COMMAND_KEY)))
+    }
+}
+
+/// `<stem>.provenance.json` next to `output`
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}.provenance.json", stem))
+}
+
+fn is_sidecar(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| n.to_string_lossy().ends_with(".provenance.json"))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// header of a recording gaitool never wrote
+    const RAW: &str = "type,exported with version\ngait,3.18.98\n";
+
+    fn provenance() -> Provenance {
+        Provenance {
+            remap: vec![
+                ("3.18.98.csv".to_string(), "aa".to_string()),
+                ("name.json".to_string(), "bb".to_string()),
+            ],
+            inputs: vec![("rec.csv".to_string(), "cc".to_string())],
+            ..Provenance::new("split", json!({"contact": "exact:1000"}))
+                .strategy(Some("centred:70".to_string()))
+        }
+    }
+
+    /// fresh dir for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-provenance-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// header text of a recording carrying `provenance`
+    fn header_text(provenance: &Provenance) -> String {
+        let mut header =
+            RecordingHeader::from_reader(RAW.as_bytes(), Path::new("rec.csv"))
+                .unwrap();
+        header.set_extra(STRATEGY_KEY, provenance.strategy.clone().unwrap());
+        provenance.write_header(&mut header);
+        let mut out = vec![];
+        header.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let text = header_text(&provenance());
+        let header =
+            RecordingHeader::from_reader(text.as_bytes(), Path::new("rec.csv"))
+                .unwrap();
+        let back = Provenance::from_header(&header, Path::new("dir/rec.csv"));
+        assert_eq!(back, Some(provenance()));
+        let raw =
+            RecordingHeader::from_reader(RAW.as_bytes(), Path::new("rec.csv"))
+                .unwrap();
+        assert_eq!(Provenance::from_header(&raw, Path::new("rec.csv")), None);
+    }
+
+    #[test]
+    fn sidecar_round_trip() {
+        let dir = test_dir("sidecar");
+        let output = dir.join("rec-result.csv");
+        provenance().write_sidecar(&output).unwrap();
+        let sidecar = sidecar_path(&output);
+        assert_eq!(sidecar, dir.join("rec-result.provenance.json"));
+        assert_eq!(Provenance::read(&output).unwrap(), provenance());
+        assert_eq!(Provenance::read(&sidecar).unwrap(), provenance());
+    }
+
+    #[test]
+    fn read_falls_back_to_header() {
+        let dir = test_dir("header");
+        let file = dir.join("rec.csv");
+        fs::write(&file, format!("{}\ntime\n0\n", header_text(&provenance())))
+            .unwrap();
+        assert_eq!(Provenance::read(&file).unwrap(), provenance());
+        assert!(matches!(
+            Provenance::read(&dir.join("missing.csv")),
+            Err(GaitError::Io { .. })
+        ));
+    }
+}
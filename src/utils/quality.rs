@@ -0,0 +1,527 @@
+use crate::error::{GaitError, Result};
+use crate::utils::events::SHANK_GYRO;
+use crate::utils::gait::{GaitEvents, Phase, Side};
+use crate::utils::util::column_f64;
+
+use polars::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// header key listing rejected strides, next to `selection`
+pub const REJECTED_KEY: &str = "rejected";
+
+/// consistency constant turning MAD into SD of normal distribution
+const MAD_SCALE: f64 = 1.4826;
+
+/// per-stride quality of one gait cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrideMetrics {
+    pub stride: Phase,
+    /// duration z-score among all strides of the recording, 0 with fewer
+    /// than two strides or identical durations
+    pub duration_z: f64,
+    /// double support, own single support, double support, other single
+    /// support, each exactly once
+    pub pattern_ok: bool,
+    /// max - min of the stride foot's shank gyroscope, NaN if missing
+    pub range: f64,
+}
+
+/// metrics of `strides`, z-score is taken among `strides`
+pub fn stride_metrics(
+    df: &DataFrame,
+    events: &GaitEvents,
+    strides: &[Phase],
+) -> Result<Vec<StrideMetrics>> {
+    let durations = strides.iter().map(Phase::duration).collect::<Vec<_>>();
+    let z = duration_z(&durations);
+    let time = column_f64(df, "time")?.unwrap_or_default();
+    let gyro = [
+        column_f64(df, SHANK_GYRO[0])?,
+        column_f64(df, SHANK_GYRO[1])?,
+    ];
+    Ok(strides
+        .iter()
+        .zip(z)
+        .map(|(s, duration_z)| {
+            let gyro = match s.side {
+                Side::Left => &gyro[0],
+                Side::Right => &gyro[1],
+            };
+            StrideMetrics {
+                stride: *s,
+                duration_z,
+                pattern_ok: pattern_ok(events, s),
+                range: gyro.as_ref().map_or(f64::NAN, |g| range(&time, g, s)),
+            }
+        })
+        .collect())
+}
+
+/// z-score of every duration, sample SD
+fn duration_z(durations: &[f64]) -> Vec<f64> {
+    let n = durations.len() as f64;
+    let mean = durations.iter().sum::<f64>() / n;
+    let sd = (durations.iter().map(|d| (d - mean).powi(2)).sum::<f64>()
+        / (n - 1.))
+        .sqrt();
+    durations
+        .iter()
+        .map(|d| match n < 2. || sd == 0. {
+            true => 0.,
+            false => (d - mean) / sd,
+        })
+        .collect()
+}
+
+/// support phases starting inside stride follow the expected order
+fn pattern_ok(events: &GaitEvents, stride: &Phase) -> bool {
+    // 0: double support, 1: own single support, 2: other single support
+    let mut kinds = vec![];
+    let phases = [
+        events.double_supports(),
+        events.single_support(stride.side),
+        events.single_support(stride.side.other()),
+    ];
+    for (kind, phases) in phases.iter().enumerate() {
+        for p in phases {
+            if stride.start <= p.start && p.start < stride.end {
+                kinds.push((p.start, kind));
+            }
+        }
+    }
+    kinds.sort_by(|a, b| a.0.total_cmp(&b.0));
+    kinds.iter().map(|(_, k)| *k).eq([0, 1, 0, 2])
+}
+
+fn range(time: &[f64], values: &[f64], stride: &Phase) -> f64 {
+    let inside = time
+        .iter()
+        .zip(values)
+        .filter(|(t, _)| stride.start <= **t && **t < stride.end)
+        .map(|(_, v)| *v);
+    let (min, max) = inside.fold((f64::MAX, f64::MIN), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    max - min
+}
+
+/// why a stride is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// duration outside fences
+    Duration,
+    /// gyroscope range outside fences
+    Range,
+    /// support phases out of order
+    Pattern,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Duration => write!(f, "duration"),
+            Reason::Range => write!(f, "range"),
+            Reason::Pattern => write!(f, "pattern"),
+        }
+    }
+}
+
+impl FromStr for Reason {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "duration" => Ok(Reason::Duration),
+            "range" => Ok(Reason::Range),
+            "pattern" => Ok(Reason::Pattern),
+            s => Err(format!("unknown reject reason '{}'", s)),
+        }
+    }
+}
+
+/// dropped stride with reasons
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    pub start: f64,
+    pub end: f64,
+    pub reasons: Vec<Reason>,
+    /// duration z-score, none if only listed in header
+    pub duration_z: Option<f64>,
+}
+
+impl Rejected {
+    /// `t` falls inside rejected stride
+    pub fn contains(&self, t: f64) -> bool {
+        self.start <= t && t < self.end
+    }
+}
+
+/// `start-end:reason|reason start-end:reason` header value
+pub fn format_rejected(rejected: &[Rejected]) -> String {
+    rejected
+        .iter()
+        .map(|r| {
+            let reasons = r.reasons.iter().map(Reason::to_string);
+            let reasons = reasons.collect::<Vec<String>>().join("|");
+            format!("{}-{}:{}", r.start, r.end, reasons)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn parse_rejected(value: &str) -> Result<Vec<Rejected>> {
+    value
+        .split_whitespace()
+        .map(|r| {
+            let invalid = || GaitError::InvalidSelection {
+                value: r.to_string(),
+            };
+            let (range, reasons) = r.split_once(':').ok_or_else(invalid)?;
+            let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+            Ok(Rejected {
+                start: start.parse().map_err(|_| invalid())?,
+                end: end.parse().map_err(|_| invalid())?,
+                reasons: reasons
+                    .split('|')
+                    .map(|r| r.parse().map_err(|_| invalid()))
+                    .collect::<Result<Vec<Reason>>>()?,
+                duration_z: None,
+            })
+        })
+        .collect()
+}
+
+/// fences of stride duration and gyroscope range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierRule {
+    /// outside `[Q1 - k * IQR, Q3 + k * IQR]`
+    Iqr(f64),
+    /// further than `k` scaled MAD from median
+    Mad(f64),
+    /// duration z-score beyond `k`, range is not checked
+    Z(f64),
+    /// duration outside `[min, max]` second, range is not checked
+    Bounds { min: f64, max: f64 },
+}
+
+impl OutlierRule {
+    /// `(low, high)` fences of values, NaN values are ignored, none if
+    /// the spread is zero, e.g. one stride or identical durations
+    fn fences(&self, values: &[f64]) -> Option<(f64, f64)> {
+        let mut sorted = values
+            .iter()
+            .cloned()
+            .filter(|v| !v.is_nan())
+            .collect::<Vec<f64>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        match *self {
+            OutlierRule::Iqr(k) => {
+                let (q1, q3) =
+                    (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+                let iqr = q3 - q1;
+                (iqr > 0.).then_some((q1 - k * iqr, q3 + k * iqr))
+            }
+            OutlierRule::Mad(k) => {
+                let median = quantile(&sorted, 0.5);
+                let mut deviations = sorted
+                    .iter()
+                    .map(|v| (v - median).abs())
+                    .collect::<Vec<f64>>();
+                deviations.sort_by(|a, b| a.total_cmp(b));
+                let mad = MAD_SCALE * quantile(&deviations, 0.5);
+                (mad > 0.).then_some((median - k * mad, median + k * mad))
+            }
+            OutlierRule::Bounds { min, max } => Some((min, max)),
+            // applied on z-scores, see `RejectPolicy::apply`
+            OutlierRule::Z(k) => Some((-k, k)),
+        }
+    }
+}
+
+/// linear interpolated quantile of sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = (sorted.len() - 1) as f64 * q;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// outlier stride rejection, default keeps every stride
+/// written as `none` or `<rule>[+pattern]`, e.g. `iqr:1.5`,
+/// `mad:3+pattern`, `bounds:0.8:1.6`, `z:2.5`, `pattern`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RejectPolicy {
+    pub rule: Option<OutlierRule>,
+    /// drop strides with support phases out of order
+    pub pattern: bool,
+}
+
+impl RejectPolicy {
+    /// split strides into kept ones and rejected ones
+    pub fn apply(
+        &self,
+        metrics: &[StrideMetrics],
+    ) -> (Vec<Phase>, Vec<Rejected>) {
+        /* z rule fences z-scores instead of durations */
+        let durations = metrics.iter().map(|m| match self.rule {
+            Some(OutlierRule::Z(_)) => m.duration_z,
+            _ => m.stride.duration(),
+        });
+        let durations = durations.collect::<Vec<f64>>();
+        let ranges = metrics.iter().map(|m| m.range).collect::<Vec<f64>>();
+        let (duration_fences, range_fences) = match self.rule {
+            Some(OutlierRule::Bounds { .. } | OutlierRule::Z(_)) => {
+                (self.rule.and_then(|r| r.fences(&durations)), None)
+            }
+            Some(rule) => (rule.fences(&durations), rule.fences(&ranges)),
+            None => (None, None),
+        };
+        let outside = |v: f64, fences: Option<(f64, f64)>| {
+            fences.is_some_and(|(lo, hi)| v < lo || v > hi)
+        };
+
+        let mut kept = vec![];
+        let mut rejected = vec![];
+        for (m, duration) in metrics.iter().zip(durations) {
+            let mut reasons = vec![];
+            if outside(duration, duration_fences) {
+                reasons.push(Reason::Duration);
+            }
+            if outside(m.range, range_fences) {
+                reasons.push(Reason::Range);
+            }
+            if self.pattern && !m.pattern_ok {
+                reasons.push(Reason::Pattern);
+            }
+            if reasons.is_empty() {
+                kept.push(m.stride);
+            } else {
+                rejected.push(Rejected {
+                    start: m.stride.start,
+                    end: m.stride.end,
+                    reasons,
+                    duration_z: Some(m.duration_z),
+                });
+            }
+        }
+        (kept, rejected)
+    }
+}
+
+impl FromStr for RejectPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let number = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", v))
+        };
+        let mut policy = Self::default();
+        for part in s.split('+') {
+            let parts = part.trim().split(':').collect::<Vec<&str>>();
+            let rule = match parts[..] {
+                ["none"] => continue,
+                ["pattern"] => {
+                    policy.pattern = true;
+                    continue;
+                }
+                ["iqr", k] => OutlierRule::Iqr(number(k)?),
+                ["mad", k] => OutlierRule::Mad(number(k)?),
+                ["z", k] => OutlierRule::Z(number(k)?),
+                ["bounds", min, max] => {
+                    let (min, max) = (number(min)?, number(max)?);
+                    if min > max {
+                        return Err(format!(
+                            "bounds min {} is above max {}",
+                            min, max
+                        ));
+                    }
+                    OutlierRule::Bounds { min, max }
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown reject policy '{}', expect none, pattern, \
+                         iqr:<k>, mad:<k>, z:<k> or bounds:<min>:<max>",
+                        part
+                    ))
+                }
+            };
+            if policy.rule.replace(rule).is_some() {
+                return Err(format!("more than one outlier rule in '{}'", s));
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for RejectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        match self.rule {
+            Some(OutlierRule::Iqr(k)) => parts.push(format!("iqr:{}", k)),
+            Some(OutlierRule::Mad(k)) => parts.push(format!("mad:{}", k)),
+            Some(OutlierRule::Z(k)) => parts.push(format!("z:{}", k)),
+            Some(OutlierRule::Bounds { min, max }) => {
+                parts.push(format!("bounds:{}:{}", min, max))
+            }
+            None => {}
+        }
+        if self.pattern {
+            parts.push("pattern".to_string());
+        }
+        if parts.is_empty() {
+            return write!(f, "none");
+        }
+        write!(f, "{}", parts.join("+"))
+    }
+}
+
+/// strides rejected by policy merged with the ones listed in header
+pub fn rejected_strides(
+    df: &DataFrame,
+    events: &GaitEvents,
+    strides: &[Phase],
+    policy: &RejectPolicy,
+    header: Option<String>,
+) -> Result<Vec<Rejected>> {
+    let metrics = stride_metrics(df, events, strides)?;
+    let (_, mut rejected) = policy.apply(&metrics);
+    let listed = match header {
+        Some(value) => parse_rejected(&value)?,
+        None => vec![],
+    };
+    for r in listed {
+        match rejected.iter_mut().find(|x| x.start == r.start) {
+            Some(x) => {
+                let new =
+                    r.reasons.into_iter().filter(|r| !x.reasons.contains(r));
+                x.reasons.extend(new.collect::<Vec<Reason>>());
+            }
+            None => {
+                let m = metrics.iter().find(|m| m.stride.start == r.start);
+                rejected.push(Rejected {
+                    duration_z: m.map(|m| m.duration_z),
+                    ..r
+                })
+            }
+        }
+    }
+    rejected.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(rejected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(durations: &[f64], ranges: &[f64]) -> Vec<StrideMetrics> {
+        let mut start = 0.;
+        durations
+            .iter()
+            .zip(ranges)
+            .map(|(d, r)| {
+                let stride = Phase {
+                    side: Side::Left,
+                    start,
+                    end: start + d,
+                };
+                start += d;
+                StrideMetrics {
+                    stride,
+                    duration_z: 0.,
+                    pattern_ok: true,
+                    range: *r,
+                }
+            })
+            .collect()
+    }
+
+    fn reasons(policy: &str, metrics: &[StrideMetrics]) -> Vec<Vec<Reason>> {
+        let policy = policy.parse::<RejectPolicy>().unwrap();
+        let (kept, rejected) = policy.apply(metrics);
+        assert_eq!(kept.len() + rejected.len(), metrics.len());
+        rejected.into_iter().map(|r| r.reasons).collect()
+    }
+
+    #[test]
+    fn iqr_fences() {
+        let rule = OutlierRule::Iqr(1.5);
+        assert_eq!(rule.fences(&[1., 2., 3., 4., 5.]), Some((-1., 7.)));
+        let m = metrics(&[1., 1.1, 1., 0.9, 1., 2.], &[5., 5., 6., 5., 4., 5.]);
+        assert_eq!(reasons("iqr:1.5", &m), vec![vec![Reason::Duration]]);
+    }
+
+    #[test]
+    fn mad_fences() {
+        let rule = OutlierRule::Mad(3.);
+        let (lo, hi) = rule.fences(&[1., 2., 3., 4., 100.]).unwrap();
+        assert!((lo - (3. - 3. * MAD_SCALE)).abs() < 1e-9);
+        assert!((hi - (3. + 3. * MAD_SCALE)).abs() < 1e-9);
+        let m = metrics(&[1., 1.1, 1., 0.9, 1.], &[4., 5., 6., 5., 50.]);
+        assert_eq!(reasons("mad:3", &m), vec![vec![Reason::Range]]);
+    }
+
+    #[test]
+    fn bounds_check_duration_only() {
+        let m = metrics(&[0.5, 1., 2.], &[5., 500., 5.]);
+        assert_eq!(
+            reasons("bounds:0.8:1.6", &m),
+            vec![vec![Reason::Duration], vec![Reason::Duration]]
+        );
+    }
+
+    #[test]
+    fn zero_spread_rejects_nothing() {
+        for rule in [OutlierRule::Iqr(1.5), OutlierRule::Mad(3.)] {
+            assert_eq!(rule.fences(&[1., 1., 1., 1., 1.2]), None);
+            assert_eq!(rule.fences(&[1.]), None);
+        }
+        let m = metrics(&[1., 1., 1., 1., 1.2], &[5., 5., 5., 5., 5.]);
+        assert!(reasons("iqr:1.5", &m).is_empty());
+        assert!(reasons("mad:3", &m[..1]).is_empty());
+    }
+
+    #[test]
+    fn duration_z_score() {
+        let z = duration_z(&[1., 2., 3.]);
+        assert_eq!(z, vec![-1., 0., 1.]);
+        // one stride or no spread has no z-score
+        assert_eq!(duration_z(&[1.]), vec![0.]);
+        assert_eq!(duration_z(&[1., 1.]), vec![0., 0.]);
+        assert!(duration_z(&[]).is_empty());
+    }
+
+    #[test]
+    fn z_rule_rejects_duration() {
+        let mut m = metrics(&[1., 1.1, 1., 0.9, 2.], &[5., 5., 5., 5., 500.]);
+        let durations =
+            m.iter().map(|m| m.stride.duration()).collect::<Vec<_>>();
+        for (m, z) in m.iter_mut().zip(duration_z(&durations)) {
+            m.duration_z = z;
+        }
+        assert_eq!(reasons("z:1.5", &m), vec![vec![Reason::Duration]]);
+        let (_, rejected) = "z:1.5".parse::<RejectPolicy>().unwrap().apply(&m);
+        assert_eq!(rejected[0].start, m[4].stride.start);
+        assert_eq!(rejected[0].duration_z, Some(m[4].duration_z));
+        assert!(m[4].duration_z > 1.5);
+    }
+
+    #[test]
+    fn policy_round_trip() {
+        for s in [
+            "none",
+            "pattern",
+            "iqr:1.5+pattern",
+            "bounds:0.8:1.6",
+            "z:2.5",
+        ] {
+            assert_eq!(s.parse::<RejectPolicy>().unwrap().to_string(), s);
+        }
+        assert!("iqr:1.5+mad:3".parse::<RejectPolicy>().is_err());
+        assert!("bounds:2:1".parse::<RejectPolicy>().is_err());
+    }
+}
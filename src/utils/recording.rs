@@ -0,0 +1,200 @@
+use crate::error::{GaitError, Result};
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// posture id in recording file name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Posture {
+    /// `1`: overground walking
+    Walking,
+    /// `2`: treadmill
+    Treadmill,
+    Other(String),
+}
+
+impl Posture {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Posture::Walking => "1",
+            Posture::Treadmill => "2",
+            Posture::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for Posture {
+    fn from(s: &str) -> Self {
+        match s {
+            "1" => Posture::Walking,
+            "2" => Posture::Treadmill,
+            s => Posture::Other(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Posture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// recording file name
+/// `{record_datetime}_{user_id}-{assistant_user_id}-{location}-{posture_id}-[{reason_id},...]-{order}.csv`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordingId {
+    pub datetime: String,
+    pub subject_id: String,
+    pub assistant_id: String,
+    pub location: String,
+    pub posture: Posture,
+    pub reasons: Vec<String>,
+    pub order: String,
+}
+
+impl RecordingId {
+    /// parse recording id from file path, file extension is ignored
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let stem =
+            path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| {
+                GaitError::FileName {
+                    path: path.to_path_buf(),
+                    reason: "no valid file stem".to_string(),
+                }
+            })?;
+        stem.parse().map_err(|reason| GaitError::FileName {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+
+    /// file name with `.csv` extension
+    pub fn file_name(&self) -> String {
+        format!("{}.csv", self)
+    }
+}
+
+impl FromStr for RecordingId {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (datetime, ids) =
+            s.split_once('_').ok_or("no '_' between datetime and ids")?;
+        if datetime.is_empty()
+            || !datetime.chars().all(|c| c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!("invalid datetime '{}'", datetime));
+        }
+        let parts = ids.split('-').collect::<Vec<&str>>();
+        if parts.len() != 6 {
+            return Err(format!(
+                "expect 6 '-' separated ids after datetime, got {}",
+                parts.len()
+            ));
+        }
+        let names = ["user id", "assistant id", "location", "posture id"];
+        for (name, part) in names.iter().zip(&parts) {
+            if part.is_empty() {
+                return Err(format!("empty {}", name));
+            }
+        }
+        let reasons = parts[4]
+            .strip_prefix('[')
+            .and_then(|r| r.strip_suffix(']'))
+            .ok_or_else(|| {
+                format!("reason ids '{}' not wrapped in [ ]", parts[4])
+            })?;
+        let reasons = if reasons.is_empty() {
+            vec![]
+        } else {
+            reasons.split(',').map(str::to_string).collect()
+        };
+        if parts[5].is_empty() {
+            return Err("empty order".to_string());
+        }
+
+        Ok(Self {
+            datetime: datetime.to_string(),
+            subject_id: parts[0].to_string(),
+            assistant_id: parts[1].to_string(),
+            location: parts[2].to_string(),
+            posture: Posture::from(parts[3]),
+            reasons,
+            order: parts[5].to_string(),
+        })
+    }
+}
+
+impl fmt::Display for RecordingId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}-{}-{}-{}-[{}]-{}",
+            self.datetime,
+            self.subject_id,
+            self.assistant_id,
+            self.location,
+            self.posture,
+            self.reasons.join(","),
+            self.order
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        let name = "2022-11-02-17-00_101-8-1-2-[1,2]-1";
+        let id: RecordingId = name.parse().unwrap();
+        assert_eq!(id.datetime, "2022-11-02-17-00");
+        assert_eq!(id.subject_id, "101");
+        assert_eq!(id.assistant_id, "8");
+        assert_eq!(id.location, "1");
+        assert_eq!(id.posture, Posture::Treadmill);
+        assert_eq!(id.reasons, vec!["1", "2"]);
+        assert_eq!(id.order, "1");
+        assert_eq!(id.to_string(), name);
+        assert_eq!(id.file_name(), format!("{}.csv", name));
+    }
+
+    #[test]
+    fn empty_reasons_round_trip() {
+        let name = "2022-11-02-17-30_102-8-1-1-[]-2";
+        let id: RecordingId = name.parse().unwrap();
+        assert!(id.reasons.is_empty());
+        assert_eq!(id.posture, Posture::Walking);
+        assert_eq!(id.to_string(), name);
+    }
+
+    #[test]
+    fn from_path_ignores_dir_and_extension() {
+        let id = RecordingId::from_path(
+            "visit-1/2022-11-02-17-30_102-8-1-3-[1]-1.csv",
+        )
+        .unwrap();
+        assert_eq!(id.posture, Posture::Other("3".to_string()));
+    }
+
+    #[test]
+    fn reject_malformed_names() {
+        for name in [
+            "sample",
+            "2022-11-02_101-8-1-2-[1]",
+            "2022-11-02_101-8-1-2-1-1",
+            "2022-11-02_-8-1-2-[1]-1",
+            "2022-11-02_101-8-1-2-[1]-",
+            "2022/11/02_101-8-1-2-[1]-1",
+        ] {
+            assert!(name.parse::<RecordingId>().is_err(), "{}", name);
+        }
+        assert!(matches!(
+            RecordingId::from_path("sample.csv"),
+            Err(GaitError::FileName { .. })
+        ));
+    }
+}
@@ -0,0 +1,585 @@
+use crate::error::{GaitError, Result};
+use crate::utils::channel::expected_names;
+use crate::utils::noraxon::NoraxonReader;
+use crate::utils::preprocess::{rename_df, LT_CONTACT, RT_CONTACT};
+use crate::utils::util::get_keys;
+
+use polars::prelude::DataFrame;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// file extensions a remap table can be written in
+pub const TABLE_EXTENSIONS: [&str; 3] = ["csv", "json", "toml"];
+
+/// generic table for English export
+pub const GENERIC_EN: &str = "all.csv";
+/// generic table for Chinese export
+pub const GENERIC_CH: &str = "all-ch.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Chinese,
+}
+
+impl Language {
+    /// Chinese export once any column name holds a CJK character
+    pub fn detect<S: AsRef<str>>(columns: &[S]) -> Self {
+        let cjk = |c: char| {
+            ('\u{4e00}'..='\u{9fff}').contains(&c)
+                || ('\u{3400}'..='\u{4dbf}').contains(&c)
+        };
+        if columns.iter().any(|c| c.as_ref().chars().any(cjk)) {
+            Language::Chinese
+        } else {
+            Language::English
+        }
+    }
+
+    pub fn generic_table(&self) -> &'static str {
+        match self {
+            Language::English => GENERIC_EN,
+            Language::Chinese => GENERIC_CH,
+        }
+    }
+}
+
+/// why a remap table is chosen
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemapReason {
+    /// table of exact export version
+    Exact,
+    /// closest lower version with same major.minor
+    ClosestLower { requested: String },
+    /// no version table, fallback to generic table by language
+    Generic {
+        requested: String,
+        language: Language,
+    },
+    /// table given by user
+    Given,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemapChoice {
+    pub table: PathBuf,
+    pub reason: RemapReason,
+    /// tables merged on top of `table`, highest priority first
+    pub extra: Vec<PathBuf>,
+}
+
+impl fmt::Display for RemapChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |p: &PathBuf| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| p.display().to_string())
+        };
+        let mut table = name(&self.table);
+        if !self.extra.is_empty() {
+            let extra = self.extra.iter().map(name).collect::<Vec<String>>();
+            table = format!("{} (+ {})", table, extra.join(", "));
+        }
+        match &self.reason {
+            RemapReason::Exact => write!(f, "remap {}: exact version", table),
+            RemapReason::ClosestLower { requested } => write!(
+                f,
+                "remap {}: closest lower version of {}",
+                table, requested
+            ),
+            RemapReason::Generic {
+                requested,
+                language,
+            } => write!(
+                f,
+                "remap {}: no table for version {}, {:?} columns detected",
+                table, requested, language
+            ),
+            RemapReason::Given => write!(f, "remap {}: given table", table),
+        }
+    }
+}
+
+/// original -> new column name mapping
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemapTable {
+    /// (original, new) pairs, highest priority first
+    pairs: Vec<(String, String)>,
+}
+
+impl RemapTable {
+    /// load table by file extension
+    /// - csv: `Original` and `New` columns
+    /// - json/toml: flat `{ original: new }` object
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !TABLE_EXTENSIONS.contains(&ext) {
+            return Err(GaitError::InvalidRemap {
+                path: path.to_path_buf(),
+                reason: format!("unsupported extension '{}'", ext),
+            });
+        }
+        if !path.is_file() {
+            return Err(GaitError::RemapNotFound {
+                path: path.to_path_buf(),
+            });
+        }
+        let invalid = |reason: String| GaitError::InvalidRemap {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let pairs = match ext {
+            "csv" => {
+                let (origs, news) = get_keys(&path.display().to_string())?;
+                origs.into_iter().zip(news).collect()
+            }
+            "json" => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(GaitError::io(path))?;
+                let map: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&text)
+                        .map_err(|e| invalid(e.to_string()))?;
+                map.into_iter()
+                    .map(|(k, v)| match v {
+                        serde_json::Value::String(v) => Ok((k, v)),
+                        _ => Err(invalid(format!("'{}' is not a string", k))),
+                    })
+                    .collect::<Result<_>>()?
+            }
+            _ => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(GaitError::io(path))?;
+                let map: toml::value::Table = toml::from_str(&text)
+                    .map_err(|e| invalid(e.to_string()))?;
+                map.into_iter()
+                    .map(|(k, v)| match v {
+                        toml::Value::String(v) => Ok((k, v)),
+                        _ => Err(invalid(format!("'{}' is not a string", k))),
+                    })
+                    .collect::<Result<_>>()?
+            }
+        };
+        Ok(Self { pairs })
+    }
+
+    /// load and merge tables, earlier table wins on same original
+    pub fn merge<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let mut table = Self::default();
+        for path in paths {
+            table.extend(Self::load(path)?);
+        }
+        Ok(table)
+    }
+
+    /// append lower priority table
+    pub fn extend(&mut self, other: Self) {
+        for (orig, new) in other.pairs {
+            if self.get(&orig).is_none() {
+                self.pairs.push((orig, new));
+            }
+        }
+    }
+
+    /// new name of original column
+    pub fn get(&self, original: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(o, _)| o == original)
+            .map(|(_, n)| n.as_str())
+    }
+
+    pub fn originals(&self) -> Vec<String> {
+        self.pairs.iter().map(|(o, _)| o.clone()).collect()
+    }
+
+    /// distinct new names in table order
+    pub fn news(&self) -> Vec<String> {
+        let mut news: Vec<String> = vec![];
+        for (_, new) in self.pairs.iter() {
+            if !news.contains(new) {
+                news.push(new.clone());
+            }
+        }
+        news
+    }
+
+    /// select and rename columns, every new name must come from one
+    /// original in data, the first one in priority order is used,
+    /// contact columns may be missing and detected from IMU later
+    pub fn apply(&self, data: &DataFrame) -> Result<DataFrame> {
+        let columns = data.get_column_names();
+        let mut origs = vec![];
+        let mut news = vec![];
+        for new in self.news() {
+            let orig = self
+                .pairs
+                .iter()
+                .filter(|(_, n)| *n == new)
+                .map(|(o, _)| o)
+                .find(|o| columns.contains(&o.as_str()));
+            match orig {
+                Some(orig) => origs.push(orig.clone()),
+                None if [LT_CONTACT, RT_CONTACT].contains(&new.as_str()) => {
+                    continue
+                }
+                None => {
+                    return Err(GaitError::MissingColumn {
+                        path: None,
+                        column: new,
+                    })
+                }
+            }
+            news.push(new);
+        }
+        let mut data = data.select(&origs)?;
+        rename_df(&mut data, &origs, &news)?;
+        Ok(data)
+    }
+}
+
+/// parse `3.18.98` to `[3, 18, 98]`
+fn parse_version(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|v| v.parse().ok()).collect()
+}
+
+/// versions which has `<version>.<csv|json|toml>` table in dir
+fn known_versions(dir: &Path) -> Result<Vec<(Vec<u32>, PathBuf)>> {
+    let paths = std::fs::read_dir(dir).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => GaitError::RemapNotFound {
+            path: dir.to_path_buf(),
+        },
+        _ => GaitError::io(dir)(e),
+    })?;
+    let mut versions = vec![];
+    for entry in paths {
+        let path = entry.map_err(GaitError::io(dir))?.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if TABLE_EXTENSIONS.contains(&ext) => {}
+            _ => continue,
+        }
+        let version = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(parse_version);
+        if let Some(version) = version {
+            versions.push((version, path));
+        }
+    }
+    Ok(versions)
+}
+
+/// every table in dir `resolve_remap` may choose, version tables and
+/// generic tables, sorted by path
+pub fn remap_candidates(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut tables = known_versions(dir)?
+        .into_iter()
+        .map(|(_, table)| table)
+        .collect::<Vec<PathBuf>>();
+    for language in [Language::English, Language::Chinese] {
+        let table = dir.join(language.generic_table());
+        if table.is_file() {
+            tables.push(table);
+        }
+    }
+    tables.sort();
+    Ok(tables)
+}
+
+/// find remap table for export version in dir
+/// 1. exact `<version>.<csv|json|toml>`
+/// 2. closest lower version with same major.minor
+/// 3. generic table of language detected from column names
+pub fn resolve_remap<S: AsRef<str>>(
+    dir: &Path,
+    version: &str,
+    columns: &[S],
+) -> Result<RemapChoice> {
+    let exact = TABLE_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", version, ext)))
+        .find(|p| p.is_file());
+    if let Some(table) = exact {
+        return Ok(RemapChoice {
+            table,
+            reason: RemapReason::Exact,
+            extra: vec![],
+        });
+    }
+
+    if let Some(requested) = parse_version(version).filter(|v| v.len() >= 2) {
+        let closest = known_versions(dir)?
+            .into_iter()
+            .filter(|(v, _)| v.len() >= 2 && v[..2] == requested[..2])
+            .filter(|(v, _)| *v < requested)
+            .max_by(|(a, _), (b, _)| a.cmp(b));
+        if let Some((_, table)) = closest {
+            return Ok(RemapChoice {
+                table,
+                reason: RemapReason::ClosestLower {
+                    requested: version.to_string(),
+                },
+                extra: vec![],
+            });
+        }
+    }
+
+    let language = Language::detect(columns);
+    let table = dir.join(language.generic_table());
+    if table.is_file() {
+        return Ok(RemapChoice {
+            table,
+            reason: RemapReason::Generic {
+                requested: version.to_string(),
+                language,
+            },
+            extra: vec![],
+        });
+    }
+    Err(GaitError::RemapNotFound {
+        path: dir.join(format!("{}.csv", version)),
+    })
+}
+
+/// problems found in a remap table
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemapReport {
+    /// `New` names produced by more than one row
+    pub duplicate_new: Vec<String>,
+    /// `Original` names not found in the recording
+    pub missing_original: Vec<String>,
+    /// recording columns the table doesn't cover
+    pub extra_columns: Vec<String>,
+    /// names chart_gui reads but the table never produces
+    pub missing_expected: Vec<String>,
+}
+
+impl RemapReport {
+    pub fn is_ok(&self) -> bool {
+        self.duplicate_new.is_empty()
+            && self.missing_original.is_empty()
+            && self.extra_columns.is_empty()
+            && self.missing_expected.is_empty()
+    }
+}
+
+impl fmt::Display for RemapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "ok");
+        }
+        let sections = [
+            ("duplicate New", &self.duplicate_new),
+            ("Original not in recording", &self.missing_original),
+            ("recording column not in table", &self.extra_columns),
+            (
+                "expected by chart_gui but not produced",
+                &self.missing_expected,
+            ),
+        ];
+        for (title, names) in sections.iter().filter(|(_, n)| !n.is_empty()) {
+            writeln!(f, "{} ({}):", title, names.len())?;
+            for name in names.iter() {
+                writeln!(f, "    {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// check merged remap tables, and against the columns of recording if given
+pub fn validate_remap<P: AsRef<Path>>(
+    tables: &[P],
+    recording: Option<&Path>,
+) -> Result<RemapReport> {
+    let remap = RemapTable::merge(tables)?;
+    let mut report = RemapReport::default();
+
+    // two originals of one table renamed to same name
+    for path in tables {
+        let table = RemapTable::load(path)?;
+        let mut seen = HashSet::new();
+        for (_, new) in table.pairs.iter() {
+            if !seen.insert(new) && !report.duplicate_new.contains(new) {
+                report.duplicate_new.push(new.clone());
+            }
+        }
+    }
+    let seen = remap.news().into_iter().collect::<HashSet<String>>();
+
+    report.missing_expected = expected_names()
+        .into_iter()
+        .filter(|name| !seen.contains(name))
+        .collect();
+
+    if let Some(recording) = recording {
+        let data = NoraxonReader::new(recording).finish()?.data;
+        let columns = data.get_column_names();
+        // with merged tables, an original is only missing when no other
+        // original of the same new name is in the recording
+        let produced = columns
+            .iter()
+            .filter_map(|c| remap.get(c))
+            .collect::<HashSet<&str>>();
+        report.missing_original = remap
+            .pairs
+            .iter()
+            .filter(|(o, n)| {
+                !columns.contains(&o.as_str()) && !produced.contains(n.as_str())
+            })
+            .map(|(o, _)| o.clone())
+            .collect();
+        report.extra_columns = columns
+            .iter()
+            .filter(|c| remap.get(c).is_none())
+            .map(|c| c.to_string())
+            .collect();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// fresh dir holding empty tables of given names
+    fn table_dir(name: &str, tables: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gaitool-remap-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for table in tables {
+            fs::write(dir.join(table), "Original,New\ntime,time\n").unwrap();
+        }
+        dir
+    }
+
+    const TABLES: [&str; 5] = [
+        "3.18.98.csv",
+        "3.18.90.json",
+        "3.17.99.csv",
+        GENERIC_EN,
+        GENERIC_CH,
+    ];
+
+    #[test]
+    fn exact_version() {
+        let dir = table_dir("exact", &TABLES);
+        let choice = resolve_remap(&dir, "3.18.98", &["time"]).unwrap();
+        assert_eq!(choice.table, dir.join("3.18.98.csv"));
+        assert_eq!(choice.reason, RemapReason::Exact);
+    }
+
+    #[test]
+    fn closest_lower_with_same_minor() {
+        let dir = table_dir("lower", &TABLES);
+        let choice = resolve_remap(&dir, "3.18.95", &["time"]).unwrap();
+        assert_eq!(choice.table, dir.join("3.18.90.json"));
+        assert_eq!(
+            choice.reason,
+            RemapReason::ClosestLower {
+                requested: "3.18.95".to_string()
+            }
+        );
+        // higher patch of same minor and other minors are never picked
+        let choice = resolve_remap(&dir, "3.18.99", &["time"]).unwrap();
+        assert_eq!(choice.table, dir.join("3.18.98.csv"));
+    }
+
+    #[test]
+    fn generic_by_detected_language() {
+        let dir = table_dir("generic", &TABLES);
+        let english = ["time", "Pelvis Accel Sensor X (mG)"];
+        let choice = resolve_remap(&dir, "3.19.1", &english).unwrap();
+        assert_eq!(choice.table, dir.join(GENERIC_EN));
+        assert_eq!(
+            choice.reason,
+            RemapReason::Generic {
+                requested: "3.19.1".to_string(),
+                language: Language::English,
+            }
+        );
+        let chinese = ["time", "骨盆 Accel 传感器 X (mG)"];
+        let choice = resolve_remap(&dir, "3.16.2", &chinese).unwrap();
+        assert_eq!(choice.table, dir.join(GENERIC_CH));
+    }
+
+    #[test]
+    fn candidates_skip_other_files() {
+        let mut tables = TABLES.to_vec();
+        tables.extend(["notes.csv", "3.18.98.bak", "filter.csv"]);
+        let dir = table_dir("candidates", &tables);
+        let mut expected = TABLES.map(|t| dir.join(t)).to_vec();
+        expected.sort();
+        assert_eq!(remap_candidates(&dir).unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_dir_is_not_found() {
+        let dir = table_dir("no-dir", &[]).join("missing");
+        assert!(matches!(
+            resolve_remap(&dir, "3.18.98", &["time"]),
+            Err(GaitError::RemapNotFound { path }) if path == dir
+        ));
+        assert!(matches!(
+            remap_candidates(&dir),
+            Err(GaitError::RemapNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn validate_tables_against_recording() {
+        let dir = table_dir("validate", &[]);
+        let table = dir.join("table.csv");
+        fs::write(&table, "Original,New\na,x\nb,x\nc,y\n").unwrap();
+        let recording = dir.join("rec.csv");
+        fs::write(
+            &recording,
+            "type,exported with version\ngait,3.18.98\n\na,d\n1,2\n",
+        )
+        .unwrap();
+
+        let report = validate_remap(&[&table], Some(&recording)).unwrap();
+        assert_eq!(report.duplicate_new, vec!["x"]);
+        // b is covered by a producing the same name
+        assert_eq!(report.missing_original, vec!["c"]);
+        assert_eq!(report.extra_columns, vec!["d"]);
+        assert_eq!(report.missing_expected.len(), expected_names().len());
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn duplicates_only_within_one_table() {
+        let dir = table_dir("validate-merge", &[]);
+        let (first, second) = (dir.join("first.json"), dir.join("second.csv"));
+        fs::write(&first, r#"{"a": "x"}"#).unwrap();
+        fs::write(&second, "Original,New\nb,x\n").unwrap();
+        let report = validate_remap(&[&first, &second], None).unwrap();
+        assert!(report.duplicate_new.is_empty());
+        assert!(report.missing_original.is_empty());
+        assert!(report.extra_columns.is_empty());
+
+        let names = expected_names();
+        let complete = dir.join("complete.csv");
+        let rows = names.iter().map(|n| format!("{},{}\n", n, n));
+        let text = format!("Original,New\n{}", rows.collect::<String>());
+        fs::write(&complete, text).unwrap();
+        assert!(validate_remap(&[&complete], None).unwrap().is_ok());
+    }
+
+    #[test]
+    fn no_table_for_language() {
+        let dir = table_dir("missing", &["3.18.98.csv", GENERIC_EN]);
+        let chinese = ["骨盆 Accel 传感器 X (mG)"];
+        assert!(matches!(
+            resolve_remap(&dir, "3.19.1", &chinese),
+            Err(GaitError::RemapNotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::{GaitError, Result};
+use crate::utils::gait::Phase;
+use crate::utils::header::format_selection;
+
+/// part of recording to summarise, every kind resolves to `[start, end)`
+/// time ranges and a stride belongs to the range its start lies in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    /// row indices into stride table, range ends at start of `end` row,
+    /// row 0 starts at 0 s and ends at the first heel strike, row `n`
+    /// is the stride from the `n`th heel strike on, as in the original
+    /// gait table
+    Index(Vec<(u32, u32)>),
+    /// time in second
+    Time(Vec<(f64, f64)>),
+    /// `selection` header of file, as written by split, an empty one
+    /// selects every stride
+    Header,
+}
+
+impl Selection {
+    /// time ranges over `strides`, `header` is the file's selection,
+    /// every range must hold at least one stride
+    pub fn resolve(
+        &self,
+        strides: &[Phase],
+        header: &[(f64, f64)],
+    ) -> Result<Vec<(f64, f64)>> {
+        // row starts: recording start, every heel strike, last stride end
+        let rows = std::iter::once(0.)
+            .chain(strides.iter().map(|s| s.start))
+            .chain(strides.last().map(|s| s.end))
+            .collect::<Vec<f64>>();
+        let ranges = match self {
+            Selection::Index(ranges) => ranges
+                .iter()
+                .map(|&(start, end)| {
+                    let (s, e) = (start as usize, end as usize);
+                    if s >= e || e >= rows.len() {
+                        return Err(GaitError::InvalidSelection {
+                            value: format!(
+                                "{} {}, expect 0 <= start < end < {}",
+                                start,
+                                end,
+                                rows.len()
+                            ),
+                        });
+                    }
+                    Ok((rows[s], rows[e]))
+                })
+                .collect::<Result<Vec<(f64, f64)>>>()?,
+            Selection::Time(ranges) => ranges.clone(),
+            Selection::Header if header.is_empty() => strides
+                .first()
+                .zip(strides.last())
+                .map(|(first, last)| vec![(first.start, last.end)])
+                .unwrap_or_default(),
+            Selection::Header => header.to_vec(),
+        };
+        if ranges.is_empty() {
+            return Err(GaitError::InvalidSelection {
+                value: String::new(),
+            });
+        }
+        for &(start, end) in &ranges {
+            let empty =
+                !strides.iter().any(|s| start <= s.start && s.start < end);
+            if end <= start || empty {
+                return Err(GaitError::InvalidSelection {
+                    value: format_selection(&[(start, end)]),
+                });
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// strides starting inside resolved ranges
+    pub fn strides(
+        &self,
+        strides: &[Phase],
+        header: &[(f64, f64)],
+    ) -> Result<Vec<Phase>> {
+        let ranges = self.resolve(strides, header)?;
+        Ok(strides
+            .iter()
+            .filter(|s| {
+                ranges.iter().any(|(a, b)| *a <= s.start && s.start < *b)
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::gait::Side;
+
+    /// strides starting at 1, 2, ..., 6, one second each, so row `n`
+    /// starts at `n` s
+    fn strides() -> Vec<Phase> {
+        (1..7)
+            .map(|i| Phase {
+                side: Side::Left,
+                start: i as f64,
+                end: i as f64 + 1.,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_ends_at_end_row_start() {
+        let selection = Selection::Index(vec![(1, 3), (4, 5)]);
+        let ranges = selection.resolve(&strides(), &[]).unwrap();
+        assert_eq!(ranges, vec![(1., 3.), (4., 5.)]);
+    }
+
+    #[test]
+    fn index_row_zero_is_before_first_strike() {
+        let selection = Selection::Index(vec![(0, 2), (6, 7)]);
+        let ranges = selection.resolve(&strides(), &[]).unwrap();
+        assert_eq!(ranges, vec![(0., 2.), (6., 7.)]);
+        // nothing starts before first heel strike
+        let selection = Selection::Index(vec![(0, 1)]);
+        assert!(selection.resolve(&strides(), &[]).is_err());
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        for range in [(2, 2), (3, 1), (0, 8)] {
+            let selection = Selection::Index(vec![range]);
+            assert!(matches!(
+                selection.resolve(&strides(), &[]),
+                Err(GaitError::InvalidSelection { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn time_and_header() {
+        let time = Selection::Time(vec![(0.5, 2.5)]);
+        assert_eq!(time.resolve(&strides(), &[]).unwrap(), vec![(0.5, 2.5)]);
+        let header = [(1., 4.)];
+        assert_eq!(
+            Selection::Header.resolve(&strides(), &header).unwrap(),
+            vec![(1., 4.)]
+        );
+    }
+
+    #[test]
+    fn empty_header_is_every_stride() {
+        let ranges = Selection::Header.resolve(&strides(), &[]).unwrap();
+        assert_eq!(ranges, vec![(1., 7.)]);
+        let all = Selection::Header.strides(&strides(), &[]).unwrap();
+        assert_eq!(all, strides());
+        assert!(Selection::Header.resolve(&[], &[]).is_err());
+    }
+
+    #[test]
+    fn strides_by_start() {
+        // stride 3-4 ends outside but starts inside
+        let selection = Selection::Time(vec![(1.5, 3.5)]);
+        let picked = selection.strides(&strides(), &[]).unwrap();
+        assert_eq!(picked, strides()[1..3]);
+    }
+
+    #[test]
+    fn empty_or_strideless_range() {
+        let empty = [
+            Selection::Time(vec![]),
+            // no stride starts inside
+            Selection::Time(vec![(1.2, 1.8)]),
+            Selection::Time(vec![(3., 2.)]),
+        ];
+        for selection in empty {
+            assert!(matches!(
+                selection.resolve(&strides(), &[]),
+                Err(GaitError::InvalidSelection { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::error::Result;
+use crate::utils::gait::{GaitEvents, Phase, Side};
+
+use polars::prelude::*;
+use serde_json::{json, Value};
+
+/// mean, sample standard deviation and coefficient of variation (%),
+/// NaN when there are not enough values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stat {
+    pub mean: f64,
+    pub sd: f64,
+    pub cv: f64,
+}
+
+impl Stat {
+    pub fn new(values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let sd = if values.len() < 2 {
+            f64::NAN
+        } else {
+            let ss = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+            (ss / (n - 1.)).sqrt()
+        };
+        Self {
+            mean,
+            sd,
+            cv: sd / mean * 100.,
+        }
+    }
+
+    fn to_json(self) -> Value {
+        json!({"mean": self.mean, "sd": self.sd, "cv": self.cv})
+    }
+}
+
+/// temporal parameters of one side, times in second
+#[derive(Debug, Clone, PartialEq)]
+pub struct SideReport {
+    pub stride: Stat,
+    /// other foot's heel strike to this one's
+    pub step: Stat,
+    pub stance: Stat,
+    /// stance in percent of its stride
+    pub stance_percent: Stat,
+    pub swing: Stat,
+    /// swing in percent of its stride
+    pub swing_percent: Stat,
+    /// both feet on ground after this foot's heel strike
+    pub initial_double_support: Stat,
+    /// both feet on ground before this foot's toe-off
+    pub terminal_double_support: Stat,
+}
+
+impl SideReport {
+    fn new(events: &GaitEvents, side: Side, ranges: &[(f64, f64)]) -> Self {
+        let durations = |phases: Vec<Phase>| {
+            Stat::new(
+                &in_ranges(phases, ranges)
+                    .iter()
+                    .map(Phase::duration)
+                    .collect::<Vec<f64>>(),
+            )
+        };
+        let strides = in_ranges(events.strides(side), ranges);
+        let stance = events.stance(side);
+        let swing = events.swing(side);
+        // percent of the stride the phase starts or ends with
+        let percent = |phases: &[Phase], by_start: bool| {
+            let values = strides
+                .iter()
+                .filter_map(|s| {
+                    phases
+                        .iter()
+                        .find(|p| {
+                            if by_start {
+                                p.start == s.start
+                            } else {
+                                p.end == s.end
+                            }
+                        })
+                        .map(|p| p.duration() / s.duration() * 100.)
+                })
+                .collect::<Vec<f64>>();
+            Stat::new(&values)
+        };
+        Self {
+            stride: durations(events.strides(side)),
+            step: durations(events.steps(side)),
+            stance_percent: percent(&stance, true),
+            swing_percent: percent(&swing, false),
+            stance: durations(stance),
+            swing: durations(swing),
+            initial_double_support: durations(events.double_support(side)),
+            terminal_double_support: durations(
+                events.double_support(side.other()),
+            ),
+        }
+    }
+
+    /// column name suffix and stat in output order
+    fn stats(&self) -> [(&'static str, Stat); 8] {
+        [
+            ("stride", self.stride),
+            ("step", self.step),
+            ("stance", self.stance),
+            ("stance %", self.stance_percent),
+            ("swing", self.swing),
+            ("swing %", self.swing_percent),
+            ("initial db", self.initial_double_support),
+            ("terminal db", self.terminal_double_support),
+        ]
+    }
+}
+
+/// spatiotemporal parameters of strides in selected ranges
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpatioTemporal {
+    /// steps per minute of each step
+    pub cadence: Stat,
+    pub left: SideReport,
+    pub right: SideReport,
+    /// step time `|L - R| / ((L + R) / 2) * 100` of each step pair
+    pub symmetry_index: Stat,
+    /// swing time, longer side over shorter side of each swing pair
+    pub symmetry_ratio: Stat,
+    /// swing time `|ln(L / R)| * 100` of each swing pair
+    pub gait_asymmetry: Stat,
+}
+
+impl SpatioTemporal {
+    /// phases starting in one of `ranges` are counted
+    pub fn new(events: &GaitEvents, ranges: &[(f64, f64)]) -> Self {
+        let mut steps = events.steps(Side::Left);
+        steps.extend(events.steps(Side::Right));
+        let cadence = in_ranges(steps, ranges)
+            .iter()
+            .map(|p| 60. / p.duration())
+            .collect::<Vec<f64>>();
+        let pairs = |phases: fn(&GaitEvents, Side) -> Vec<Phase>| {
+            paired(
+                &in_ranges(phases(events, Side::Left), ranges),
+                &in_ranges(phases(events, Side::Right), ranges),
+            )
+        };
+        let (steps, swings) =
+            (pairs(GaitEvents::steps), pairs(GaitEvents::swing));
+        let stat = |pairs: &[(f64, f64)], f: fn(f64, f64) -> f64| {
+            Stat::new(
+                &pairs.iter().map(|(l, r)| f(*l, *r)).collect::<Vec<f64>>(),
+            )
+        };
+        Self {
+            cadence: Stat::new(&cadence),
+            symmetry_index: stat(&steps, |l, r| {
+                (l - r).abs() / ((l + r) / 2.) * 100.
+            }),
+            symmetry_ratio: stat(&swings, |l, r| l.max(r) / l.min(r)),
+            gait_asymmetry: stat(&swings, |l, r| (l / r).ln().abs() * 100.),
+            left: SideReport::new(events, Side::Left, ranges),
+            right: SideReport::new(events, Side::Right, ranges),
+        }
+    }
+
+    pub fn side(&self, side: Side) -> &SideReport {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    /// one row, `<name> mean/sd/cv` columns
+    pub fn to_df(&self) -> Result<DataFrame> {
+        let mut columns = vec![];
+        let mut push = |name: String, stat: Stat| {
+            columns.push(Series::new(&format!("{} mean", name), [stat.mean]));
+            columns.push(Series::new(&format!("{} sd", name), [stat.sd]));
+            columns.push(Series::new(&format!("{} cv", name), [stat.cv]));
+        };
+        push("cadence".to_string(), self.cadence);
+        for side in Side::BOTH {
+            let prefix = side.to_string().to_lowercase();
+            for (name, stat) in self.side(side).stats() {
+                push(format!("{} {}", prefix, name), stat);
+            }
+        }
+        push("step si".to_string(), self.symmetry_index);
+        push("swing ratio".to_string(), self.symmetry_ratio);
+        push("gait asymmetry".to_string(), self.gait_asymmetry);
+        Ok(DataFrame::new(columns)?)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let side = |side: Side| {
+            let stats = self.side(side).stats();
+            Value::Object(
+                stats
+                    .iter()
+                    .map(|(name, stat)| (name.to_string(), stat.to_json()))
+                    .collect(),
+            )
+        };
+        json!({
+            "cadence": self.cadence.to_json(),
+            "LT": side(Side::Left),
+            "RT": side(Side::Right),
+            "symmetry": {
+                "step si": self.symmetry_index.to_json(),
+                "swing ratio": self.symmetry_ratio.to_json(),
+                "gait asymmetry": self.gait_asymmetry.to_json(),
+            },
+        })
+    }
+}
+
+/// durations of every left phase and the first right phase starting
+/// from it on, before the next left phase
+fn paired(left: &[Phase], right: &[Phase]) -> Vec<(f64, f64)> {
+    left.iter()
+        .enumerate()
+        .filter_map(|(i, l)| {
+            let next = left.get(i + 1).map_or(f64::INFINITY, |n| n.start);
+            right
+                .iter()
+                .find(|r| l.start <= r.start && r.start < next)
+                .map(|r| (l.duration(), r.duration()))
+        })
+        .collect()
+}
+
+fn in_ranges(phases: Vec<Phase>, ranges: &[(f64, f64)]) -> Vec<Phase> {
+    phases
+        .into_iter()
+        .filter(|p| ranges.iter().any(|(s, e)| *s <= p.start && p.start < *e))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::gait::FootEvents;
+
+    /// right swings 1-4, 11-15, 21-24, left swings 6-10, 16-20, 26-30
+    fn events() -> GaitEvents {
+        GaitEvents {
+            left: FootEvents {
+                initial_contact: vec![0., 10., 20., 30.],
+                toe_off: vec![6., 16., 26.],
+            },
+            right: FootEvents {
+                initial_contact: vec![4., 15., 24.],
+                toe_off: vec![1., 11., 21.],
+            },
+        }
+    }
+
+    fn assert_stat(stat: Stat, values: &[f64]) {
+        let expected = Stat::new(values);
+        for (a, b) in [
+            (stat.mean, expected.mean),
+            (stat.sd, expected.sd),
+            (stat.cv, expected.cv),
+        ] {
+            let same = (a.is_nan() && b.is_nan()) || (a - b).abs() < 1e-9;
+            assert!(same, "{:?} != {:?}", stat, expected);
+        }
+    }
+
+    #[test]
+    fn stat_of_values() {
+        let stat = Stat::new(&[2., 4., 6.]);
+        assert_eq!((stat.mean, stat.sd, stat.cv), (4., 2., 50.));
+        assert!(Stat::new(&[1.]).sd.is_nan());
+    }
+
+    #[test]
+    fn side_durations() {
+        let report = SpatioTemporal::new(&events(), &[(0., 40.)]);
+        assert_stat(report.left.stride, &[10., 10., 10.]);
+        // left steps 4-10, 15-20, 24-30, right steps 0-4, 10-15, 20-24
+        assert_stat(report.left.step, &[6., 5., 6.]);
+        assert_stat(report.right.step, &[4., 5., 4.]);
+        assert_stat(report.left.swing, &[4., 4., 4.]);
+        assert_stat(report.right.swing, &[3., 4., 3.]);
+        let cadence = [6., 5., 6., 4., 5., 4.].map(|d| 60. / d);
+        assert_stat(report.cadence, &cadence);
+    }
+
+    #[test]
+    fn symmetry_of_each_pair() {
+        let report = SpatioTemporal::new(&events(), &[(0., 40.)]);
+        // step pairs (6, 5) and (5, 4), last left step has no right one
+        assert_stat(report.symmetry_index, &[1. / 5.5 * 100., 1. / 4.5 * 100.]);
+        // swing pairs (4, 4) and (4, 3)
+        assert_stat(report.symmetry_ratio, &[1., 4. / 3.]);
+        assert_stat(report.gait_asymmetry, &[0., (4f64 / 3.).ln() * 100.]);
+
+        let df = report.to_df().unwrap();
+        let si = df.column("step si mean").unwrap().f64().unwrap().get(0);
+        assert_eq!(si, Some(report.symmetry_index.mean));
+        assert!(report.to_json()["symmetry"]["swing ratio"]["sd"].is_f64());
+    }
+
+    #[test]
+    fn only_pairs_in_ranges() {
+        let report = SpatioTemporal::new(&events(), &[(0., 12.)]);
+        assert_stat(report.left.stride, &[10., 10.]);
+        assert_stat(report.symmetry_index, &[1. / 5.5 * 100.]);
+        assert_stat(report.gait_asymmetry, &[0.]);
+    }
+}
@@ -1,170 +1,113 @@
+use crate::error::{GaitError, Result};
+use crate::utils::header::{parse_selection, SELECTION_KEY};
+
 use polars::prelude::{
-    CsvReader, CsvWriter, DataFrame, Result, SerReader, SerWriter,
+    CsvReader, CsvWriter, DataFrame, DataType, SerReader, SerWriter,
     TakeRandomUtf8,
 };
 use serde_json::{json, Value};
-use std::io::prelude::*;
-use std::io::{LineWriter, Write};
 use std::path::Path;
 
 /// get remap column name csv
 pub fn get_keys(path: &str) -> Result<(Vec<String>, Vec<String>)> {
+    if !Path::new(path).is_file() {
+        return Err(GaitError::RemapNotFound { path: path.into() });
+    }
     let dict = CsvReader::from_path(path)?.finish()?;
-    let ori_key = dict["Original"].utf8()?.into_iter().fold(
-        Vec::with_capacity(dict.height()),
-        |mut v, k| {
-            v.push(k.unwrap().to_string());
-            v
-        },
-    );
-    let new_key = dict["New"].utf8()?.into_iter().fold(
-        Vec::with_capacity(dict.height()),
-        |mut v, k| {
-            v.push(k.unwrap().to_string());
-            v
-        },
-    );
-    return Ok((ori_key, new_key));
-}
-
-/// extract header to smaller file
-pub fn extract_header(path: &str, output: &str) {
-    let raw_file = std::fs::File::open(&path).expect("Can't open raw file");
-    let reader_raw = std::io::BufReader::new(raw_file);
-    let header_file =
-        std::fs::File::create(output).expect("Can't create header_file");
-    let mut writer_header = LineWriter::new(header_file);
-    reader_raw.lines().take(2).for_each(|l| {
-        let mut l = l.unwrap();
-        l.push('\n');
-        writer_header
-            .write_all(l.as_bytes())
-            .expect("write line to header");
-    });
+    let ori_key = dict["Original"]
+        .utf8()?
+        .into_iter()
+        .map(|k| k.unwrap_or_default().to_string())
+        .collect();
+    let new_key = dict["New"]
+        .utf8()?
+        .into_iter()
+        .map(|k| k.unwrap_or_default().to_string())
+        .collect();
+    Ok((ori_key, new_key))
 }
 
 /// write df into new csv
 pub fn save_csv<'a>(
-    mut df: &mut DataFrame,
+    df: &mut DataFrame,
     save_dir: &'a str,
     filename: &'a str,
-) -> &'a str {
+) -> Result<&'a str> {
     let file_path = Path::new(&save_dir).join(Path::new(&filename));
-    let mut file = std::fs::File::create(file_path).unwrap();
+    let mut file =
+        std::fs::File::create(&file_path).map_err(GaitError::io(&file_path))?;
 
     CsvWriter::new(&mut file)
         .has_header(true)
         .with_delimiter(b',')
-        .finish(&mut df)
-        .unwrap();
-    filename
+        .finish(df)?;
+    Ok(filename)
 }
 
-/// append df to header.csv
-pub fn append_df2header<'a>(
-    mut df: &mut DataFrame,
-    save_dir: &'a str,
-    filename: &'a str,
-) -> &'a str {
-    let file_path = Path::new(&save_dir).join(Path::new(&filename));
-    let mut file = std::fs::OpenOptions::new()
-        .append(true)
-        .open(file_path)
-        .unwrap();
-
-    writeln!(file, "").expect("Err add blank line between header and data");
+/// column `name` of df as f64 with nulls as 0, none if df has no such
+/// column
+pub fn column_f64(df: &DataFrame, name: &str) -> Result<Option<Vec<f64>>> {
+    match df.column(name) {
+        Ok(s) => Ok(Some(
+            s.cast(&DataType::Float64)?
+                .f64()?
+                .into_iter()
+                .map(|v| v.unwrap_or(0.))
+                .collect(),
+        )),
+        Err(_) => Ok(None),
+    }
+}
 
-    CsvWriter::new(&mut file)
-        .has_header(true)
-        .with_delimiter(b',')
-        .finish(&mut df)
-        .unwrap();
-    filename
+/// lower case hex of bytes, e.g. a digest
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 /// get range from header df
-pub fn get_range(df: &DataFrame) -> Vec<Value> {
-    match df.column("selection") {
-        Ok(s) => match s.utf8().unwrap().get(0) {
-            Some(ranges) => {
-                let ranges = ranges
-                    .split(' ')
-                    .map(str::to_string)
-                    .collect::<Vec<String>>();
-                ranges.iter().fold(
-                    Vec::with_capacity(ranges.len()),
-                    |mut v, r| {
-                        let range = r
-                            .split('-')
-                            .take(2)
-                            .map(str::to_string)
-                            .collect::<Vec<String>>();
-                        v.push(json!({
-                            "Start": range[0].parse::<f64>().unwrap(),
-                            "End": range[1].parse::<f64>().unwrap()
-                        }));
-                        v
-                    },
-                )
-            }
-            None => {
-                vec![]
-            }
+pub fn get_range(df: &DataFrame) -> Result<Vec<Value>> {
+    let ranges = match df.column(SELECTION_KEY) {
+        Ok(s) => match s.utf8()?.get(0) {
+            Some(ranges) => parse_selection(ranges)?,
+            None => return Ok(vec![]),
         },
-        Err(..) => vec![],
-    }
+        Err(..) => return Ok(vec![]),
+    };
+    Ok(ranges
+        .into_iter()
+        .map(|(start, end)| json!({"Start": start, "End": end}))
+        .collect())
 }
 
-pub fn get_file_name<P: AsRef<Path>>(input_path: P) -> String {
-    input_path
-        .as_ref()
-        .file_name()
-        .unwrap_or_else(|| {
-            panic!(
-                "Couldn't get file name from {}",
-                input_path.as_ref().display()
-            )
-        })
-        .to_str()
-        .unwrap_or_else(|| {
-            panic!(
-                "Couldn't parse file name to str from {}",
-                input_path.as_ref().display()
-            )
+pub fn get_file_name<P: AsRef<Path>>(input_path: P) -> Result<String> {
+    let path = input_path.as_ref();
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| GaitError::FileName {
+            path: path.to_path_buf(),
+            reason: "no valid file name".to_string(),
         })
-        .to_string()
 }
 
-pub fn get_file_stem<P: AsRef<Path>>(input_path: P) -> String {
-    input_path
-        .as_ref()
-        .file_stem()
-        .unwrap_or_else(|| {
-            panic!(
-                "Couldn't get file stem from {}",
-                input_path.as_ref().display()
-            )
-        })
-        .to_str()
-        .unwrap_or_else(|| {
-            panic!(
-                "Couldn't parse file stem to str from {}",
-                input_path.as_ref().display()
-            )
+pub fn get_file_stem<P: AsRef<Path>>(input_path: P) -> Result<String> {
+    let path = input_path.as_ref();
+    path.file_stem()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| GaitError::FileName {
+            path: path.to_path_buf(),
+            reason: "no valid file stem".to_string(),
         })
-        .to_string()
 }
 
-pub fn join_path<P: AsRef<Path>>(path: P, input: P) -> String {
-    path.as_ref()
-        .join(input.as_ref())
+pub fn join_path<P: AsRef<Path>>(path: P, input: P) -> Result<String> {
+    let joined = path.as_ref().join(input.as_ref());
+    joined
         .to_str()
-        .unwrap_or_else(|| {
-            panic!(
-                "Couldn't join path from {} to {}",
-                path.as_ref().display(),
-                input.as_ref().display()
-            )
+        .map(str::to_string)
+        .ok_or(GaitError::FileName {
+            path: joined.clone(),
+            reason: "path is not valid utf-8".to_string(),
         })
-        .to_string()
 }